@@ -0,0 +1,135 @@
+//! Command line tools for xml1 documents
+
+use std::{
+    env,
+    io::{self, Read, Write},
+    process::ExitCode,
+};
+
+use xml1::pretty::{self, FormatOptions};
+
+const USAGE: &str = "\
+usage: xml1 <command> [options] [FILE...]
+
+Files are modified in place, stdin is read and written to stdout when no files are given.
+
+commands:
+  fmt     re-indent documents
+            --indent N   spaces for each level (default 2)
+            --width N    wrap attributes one per line past N columns
+            --check      only report files that aren't formatted
+  minify  remove comments and insignificant whitespace, reports the bytes saved
+";
+
+fn main() -> ExitCode {
+    let mut args = env::args().skip(1);
+    let result = match args.next().as_deref() {
+        Some("fmt") => fmt(args),
+        Some("minify") => minify(args),
+        _ => Err(USAGE.into()),
+    };
+    match result {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn fmt(args: impl Iterator<Item = String>) -> Result<ExitCode, String> {
+    let mut options = FormatOptions::default();
+    let mut check = false;
+    let mut files = Vec::new();
+
+    let mut args = args.peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--indent" => options.indent = number(&arg, args.next())?,
+            "--width" => options.max_width = Some(number(&arg, args.next())?),
+            "--check" => check = true,
+            _ if arg.starts_with("--") => {
+                return Err(format!("unknown option `{}`\n\n{}", arg, USAGE))
+            }
+            _ => files.push(arg),
+        }
+    }
+
+    let mut code = ExitCode::SUCCESS;
+    transform(
+        &files,
+        |src| pretty::format_to_string(src, &options),
+        |path, src, out| {
+            if !check {
+                return false;
+            }
+            if src != out {
+                println!("{}", path);
+                code = ExitCode::FAILURE;
+            }
+            true
+        },
+    )?;
+    Ok(code)
+}
+
+fn minify(args: impl Iterator<Item = String>) -> Result<ExitCode, String> {
+    let files: Vec<_> = args.collect();
+    if let Some(arg) = files.iter().find(|arg| arg.starts_with("--")) {
+        return Err(format!("unknown option `{}`\n\n{}", arg, USAGE));
+    }
+
+    transform(
+        &files,
+        |src| {
+            let mut out = String::with_capacity(src.len());
+            xml1::minify(src, &mut out).expect("a String never fails to format");
+            out
+        },
+        |path, src, out| {
+            eprintln!(
+                "{}: saved {} bytes",
+                path,
+                src.len().saturating_sub(out.len())
+            );
+            false
+        },
+    )?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Applies `f` to each file, or stdin, `skip_write` can intercept the output before it's written
+fn transform(
+    files: &[String],
+    f: impl Fn(&str) -> String,
+    mut skip_write: impl FnMut(&str, &str, &str) -> bool,
+) -> Result<(), String> {
+    if files.is_empty() {
+        let mut src = String::new();
+        io::stdin()
+            .read_to_string(&mut src)
+            .map_err(|err| format!("stdin: {}", err))?;
+        let out = f(&src);
+        if !skip_write("<stdin>", &src, &out) {
+            io::stdout()
+                .write_all(out.as_bytes())
+                .map_err(|err| format!("stdout: {}", err))?;
+        }
+        return Ok(());
+    }
+
+    for path in files {
+        let src = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+        let out = f(&src);
+        if !skip_write(path, &src, &out) && out != src {
+            std::fs::write(path, out).map_err(|err| format!("{}: {}", path, err))?;
+        }
+    }
+    Ok(())
+}
+
+fn number(option: &str, value: Option<String>) -> Result<usize, String> {
+    value
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| format!("`{}` expects a number", option))
+}
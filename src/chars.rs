@@ -1,75 +1,162 @@
 #![allow(unused)]
 
-use core::{ptr::null, slice, str::from_utf8_unchecked};
+use core::{slice, str::from_utf8_unchecked};
 
-/// Similar to [`core::str::Chars`] but it can peek and retain pointer information
+use crate::scan;
+
+/// Similar to [`core::str::Chars`] but it can peek and retain position information.
+///
+/// Every delimiter the parser looks for is ASCII, so scanning is done over the raw bytes,
+/// code points are only decoded when a non ASCII byte must be classified
+#[derive(Clone)]
 pub struct Chars<'a> {
     src: &'a str,
-    iter: slice::Iter<'a, u8>,
-    ptr: *const u8,
-    ch: Option<char>,
+    pos: usize,
 }
 
 /// Points to a valid UTF8 character inside a [`str`], used to take sub strings
 #[derive(Copy, Clone)]
 pub struct Cursor {
-    ptr: *const u8,
+    pos: usize,
 }
 
 impl<'a> Chars<'a> {
     #[inline(always)]
     pub fn offset_from_source_str(&self) -> usize {
-        unsafe { self.ptr.offset_from(self.src.as_ptr()) as _ }
+        self.pos
+    }
+
+    /// Moves to the byte offset `pos` of the source, which must be a char boundary
+    #[inline(always)]
+    pub fn seek(&mut self, pos: usize) {
+        assert!(self.src.is_char_boundary(pos), "not a char boundary");
+        self.pos = pos;
     }
 
     #[inline(always)]
     pub fn cursor(&self) -> Cursor {
-        Cursor { ptr: self.ptr }
+        Cursor { pos: self.pos }
     }
 
     #[inline(always)]
     pub fn head(&self) -> Option<char> {
-        self.ch
+        let byte = self.head_byte()?;
+        if byte < 0x80 {
+            Some(byte as char)
+        } else {
+            let mut iter = self.src.as_bytes()[self.pos..].iter();
+            // SAFETY: `pos` is always at a char boundary of a valid UTF-8 string
+            next_code_point(&mut iter).map(|ch| unsafe { char::from_u32_unchecked(ch) })
+        }
     }
 
+    /// First byte of the head char
     #[inline(always)]
-    pub fn sub_str_from_cursor(&self, cursor: Cursor) -> &'a str {
-        unsafe {
-            let offset = cursor.ptr.offset_from(self.src.as_ptr());
-            assert!(
-                0 <= offset && (offset as usize) < self.src.len(),
-                "cursor is from a different str"
-            );
-            from_utf8_unchecked(slice::from_raw_parts(
-                cursor.ptr,
-                self.ptr.offset_from(cursor.ptr) as _,
-            ))
+    pub fn head_byte(&self) -> Option<u8> {
+        self.src.as_bytes().get(self.pos).copied()
+    }
+
+    /// Byte `n` bytes after the head
+    #[inline(always)]
+    pub fn peek_byte(&self, n: usize) -> Option<u8> {
+        self.src.as_bytes().get(self.pos + n).copied()
+    }
+
+    /// Advances `n` bytes, the new position must be a char boundary
+    #[inline(always)]
+    pub fn advance(&mut self, n: usize) {
+        self.pos += n;
+        debug_assert!(self.src.is_char_boundary(self.pos));
+    }
+
+    /// Advances until the head byte matches `f`, returns the matched byte or `None` at the end of the input
+    ///
+    /// `f` must only match ASCII bytes so the scan always stops at a char boundary
+    #[inline(always)]
+    pub fn skip_until(&mut self, f: impl Fn(u8) -> bool) -> Option<u8> {
+        let bytes = &self.src.as_bytes()[self.pos..];
+        match bytes.iter().position(|&b| f(b)) {
+            Some(i) => {
+                self.pos += i;
+                Some(bytes[i])
+            }
+            None => {
+                self.pos = self.src.len();
+                None
+            }
+        }
+    }
+
+    /// Same as [`Chars::skip_until`] but looks for either `a` or `b` using vectorized searches,
+    /// both must be ASCII
+    #[inline(always)]
+    pub fn skip_until2(&mut self, a: u8, b: u8) -> Option<u8> {
+        debug_assert!(a.is_ascii() && b.is_ascii());
+        let bytes = &self.src.as_bytes()[self.pos..];
+        match scan::memchr2(a, b, bytes) {
+            Some(i) => {
+                self.pos += i;
+                Some(bytes[i])
+            }
+            None => {
+                self.pos = self.src.len();
+                None
+            }
+        }
+    }
+
+    /// Advances past the next occurrence of `pat`, returns `false` and moves
+    /// to the end of the input when not found
+    #[inline(always)]
+    pub fn skip_past(&mut self, pat: &str) -> bool {
+        match scan::find(pat.as_bytes(), self.rest().as_bytes()) {
+            Some(i) => {
+                self.pos += i + pat.len();
+                true
+            }
+            None => {
+                self.pos = self.src.len();
+                false
+            }
         }
     }
 
+    #[inline(always)]
+    pub fn sub_str_from_cursor(&self, cursor: Cursor) -> &'a str {
+        assert!(cursor.pos <= self.pos, "cursor is ahead of the input");
+        // SAFETY: both positions are char boundaries of `src`
+        unsafe { self.src.get_unchecked(cursor.pos..self.pos) }
+    }
+
     #[inline(always)]
     pub fn source(&self) -> &'a str {
         self.src
     }
 
+    /// Remaining input, including the head
+    #[must_use]
+    #[inline(always)]
+    pub fn rest(&self) -> &'a str {
+        // SAFETY: `pos` is always at a char boundary of `src`
+        unsafe { self.src.get_unchecked(self.pos..) }
+    }
+
+    /// Remaining input after the head
     #[must_use]
     #[inline(always)]
     pub fn tail(&self) -> &'a str {
-        // SAFETY: `Chars` is only made from a str, which guarantees the iter is valid UTF-8.
-        unsafe { from_utf8_unchecked(self.iter.as_slice()) }
+        let rest = self.rest();
+        match self.head() {
+            // SAFETY: `ch` is the first char of `rest`
+            Some(ch) => unsafe { rest.get_unchecked(ch.len_utf8()..) },
+            None => rest,
+        }
     }
 }
 
 impl<'a> From<&'a str> for Chars<'a> {
     fn from(src: &'a str) -> Self {
-        let mut chars = Chars {
-            src,
-            iter: src.as_bytes().iter(),
-            ptr: null(),
-            ch: None,
-        };
-        chars.next();
-        chars
+        Chars { src, pos: 0 }
     }
 }
 
@@ -78,12 +165,9 @@ impl<'a> Iterator for Chars<'a> {
 
     #[inline(always)]
     fn next(&mut self) -> Option<char> {
-        let tmp = self.ch;
-        self.ptr = self.iter.as_slice().as_ptr();
-        // SAFETY: `str` invariant says `self.iter` is a valid UTF-8 string and
-        // the resulting `ch` is a valid Unicode Scalar Value.
-        self.ch = next_code_point(&mut self.iter).map(|ch| unsafe { char::from_u32_unchecked(ch) });
-        tmp
+        let ch = self.head()?;
+        self.pos += ch.len_utf8();
+        Some(ch)
     }
 }
 
@@ -151,9 +235,36 @@ mod tests {
         assert_eq!(chars.head(), Some('a'));
         assert_eq!(chars.next(), Some('a'));
         assert_eq!(chars.head(), Some('b'));
-        // assert_eq!(chars.remainer_str(), "cd");
+        assert_eq!(chars.tail(), "cd");
         assert_eq!(chars.next(), Some('b'));
         assert_eq!(chars.head(), Some('c'));
-        // assert_eq!(chars.remainer_str(), "d");
+        assert_eq!(chars.tail(), "d");
+    }
+
+    #[test]
+    fn multibyte() {
+        let mut chars = Chars::from("aé☕b");
+        assert_eq!(chars.next(), Some('a'));
+        assert_eq!(chars.head_byte(), Some(0xC3));
+        assert_eq!(chars.head(), Some('é'));
+        assert_eq!(chars.tail(), "☕b");
+        assert_eq!(chars.next(), Some('é'));
+        assert_eq!(chars.next(), Some('☕'));
+        assert_eq!(chars.offset_from_source_str(), 6);
+        assert_eq!(chars.next(), Some('b'));
+        assert_eq!(chars.next(), None);
+        assert_eq!(chars.head(), None);
+    }
+
+    #[test]
+    fn skip() {
+        let mut chars = Chars::from("サイ<b>c-->d");
+        let cursor = chars.cursor();
+        assert_eq!(chars.skip_until(|b| b == b'<'), Some(b'<'));
+        assert_eq!(chars.sub_str_from_cursor(cursor), "サイ");
+        assert!(chars.skip_past("-->"));
+        assert_eq!(chars.rest(), "d");
+        assert!(!chars.skip_past("-->"));
+        assert_eq!(chars.head(), None);
     }
 }
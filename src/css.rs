@@ -0,0 +1,823 @@
+//! CSS selectors matched against a [`Document`], or an [`XmlIter`] with a stack of ancestors.
+//!
+//! Supported are type selectors and `*`, `#id`, `.class` (a `class` attribute is a list of
+//! space separated tokens), attribute selectors with the `=`, `~=`, `|=`, `^=`, `$=` and `*=`
+//! operators, `:first-child`, `:last-child`, `:nth-child(an+b)`, the descendant, child (`>`),
+//! next sibling (`+`) and subsequent sibling (`~`) combinators and lists separated by `,`.
+//!
+//! ```rust
+//! # use xml1::{css::Selector, dom::Document};
+//! let src = "<ui><button class=\"big primary\"><label/></button><button><label/></button></ui>";
+//! let selector = Selector::parse("button.primary > label").unwrap();
+//!
+//! let doc = Document::parse(src);
+//! let labels = selector.select(&doc);
+//! assert_eq!(labels.len(), 1);
+//! assert_eq!(labels[0].parent().unwrap().attr("class"), Some("big primary"));
+//! ```
+
+use alloc::{collections::VecDeque, vec, vec::Vec};
+use core::{fmt, ops::Range};
+
+use crate::{
+    dom::{Document, NodeRef},
+    scan, XmlEvent, XmlIter,
+};
+
+/// Error of [`Selector::parse`] and [`Selector::stream`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelectorError {
+    /// Byte offset in the selector
+    pub position: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for SelectorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.message, self.position)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Combinator {
+    /// Whitespace
+    Descendant,
+    /// `>`
+    Child,
+    /// `+`
+    NextSibling,
+    /// `~`
+    SubsequentSibling,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AttrOp {
+    Exists,
+    /// `=`
+    Equals,
+    /// `~=`
+    Includes,
+    /// `|=`
+    DashMatch,
+    /// `^=`
+    Prefix,
+    /// `$=`
+    Suffix,
+    /// `*=`
+    Substring,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Filter<'s> {
+    Attr {
+        name: &'s str,
+        op: AttrOp,
+        value: &'s str,
+    },
+    /// `:nth-child(an+b)`, `:first-child` is `0n+1`
+    NthChild {
+        a: i64,
+        b: i64,
+    },
+    LastChild,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Compound<'s> {
+    /// `None` for `*`
+    name: Option<&'s str>,
+    filters: Vec<Filter<'s>>,
+}
+
+/// Compounds from left to right, each with the combinator that links it to the previous one
+type Complex<'s> = Vec<(Combinator, Compound<'s>)>;
+
+/// Parsed list of selectors, see the [module docs](self)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selector<'s> {
+    list: Vec<Complex<'s>>,
+}
+
+impl<'s> Selector<'s> {
+    pub fn parse(selector: &'s str) -> Result<Self, SelectorError> {
+        let mut parser = Parser {
+            src: selector,
+            pos: 0,
+        };
+        let mut list = Vec::new();
+        loop {
+            list.push(parser.complex()?);
+            parser.ws();
+            if parser.pos == selector.len() {
+                break;
+            }
+            if !parser.eat(",") {
+                return Err(parser.error("unexpected character"));
+            }
+        }
+        Ok(Self { list })
+    }
+
+    /// True if the selector can run with [`Selector::stream`], `:last-child` isn't supported
+    pub fn is_streamable(&self) -> bool {
+        self.list
+            .iter()
+            .flatten()
+            .flat_map(|(_, compound)| &compound.filters)
+            .all(|filter| *filter != Filter::LastChild)
+    }
+
+    /// True if the element `node` matches any of the selectors
+    pub fn matches(&self, node: NodeRef<'_, '_>) -> bool {
+        node.is_element() && self.matches_element(node)
+    }
+
+    /// Matching elements in document order
+    pub fn select<'d, 'a>(&self, doc: &'d Document<'a>) -> Vec<NodeRef<'d, 'a>> {
+        doc.nodes().filter(|&node| self.matches(node)).collect()
+    }
+
+    /// Matching descendants of `node` in document order
+    pub fn select_from<'d, 'a>(&self, node: NodeRef<'d, 'a>) -> Vec<NodeRef<'d, 'a>> {
+        node.descendants()
+            .skip(1)
+            .filter(|&node| self.matches(node))
+            .collect()
+    }
+
+    /// Matches the elements of `iter` as they are read, see [`Selector::is_streamable`]
+    pub fn stream<'x, 'a>(&'x self, iter: XmlIter<'a>) -> Result<Stream<'x, 'a>, SelectorError> {
+        if !self.is_streamable() {
+            return Err(SelectorError {
+                position: 0,
+                message: "selector can't be streamed",
+            });
+        }
+        Ok(Stream {
+            selector: self,
+            iter,
+            siblings: self.prev_siblings(),
+            elements: Vec::new(),
+            attrs: Vec::new(),
+            open: Vec::new(),
+            pending: None,
+            queue: VecDeque::new(),
+        })
+    }
+
+    /// How many previous siblings of an element the selector looks at, `None` for all of them
+    fn prev_siblings(&self) -> Option<usize> {
+        let mut max = 0;
+        for complex in &self.list {
+            let mut run = 0;
+            for (combinator, _) in &complex[1..] {
+                match combinator {
+                    Combinator::SubsequentSibling => return None,
+                    Combinator::NextSibling => run += 1,
+                    _ => run = 0,
+                }
+                max = max.max(run);
+            }
+        }
+        Some(max)
+    }
+
+    fn matches_element<'a, E: Element<'a>>(&self, element: E) -> bool {
+        self.list
+            .iter()
+            .any(|complex| matches_from(complex, complex.len() - 1, element))
+    }
+}
+
+/// What selectors are matched against
+trait Element<'a>: Copy {
+    fn name(&self) -> &'a str;
+    /// Value of the attribute, valueless attributes are empty
+    fn attr(&self, name: &str) -> Option<&'a str>;
+    fn parent(&self) -> Option<Self>;
+    /// Previous element sibling
+    fn prev_sibling(&self) -> Option<Self>;
+    /// One based position among the element siblings
+    fn index(&self) -> usize;
+    fn is_last(&self) -> bool;
+}
+
+impl<'a> Element<'a> for NodeRef<'_, 'a> {
+    fn name(&self) -> &'a str {
+        NodeRef::name(self).unwrap()
+    }
+
+    fn attr(&self, name: &str) -> Option<&'a str> {
+        NodeRef::attr(self, name)
+    }
+
+    fn parent(&self) -> Option<Self> {
+        NodeRef::parent(self).filter(|node| node.is_element())
+    }
+
+    fn prev_sibling(&self) -> Option<Self> {
+        core::iter::successors(NodeRef::prev_sibling(self), NodeRef::prev_sibling)
+            .find(|node| node.is_element())
+    }
+
+    fn index(&self) -> usize {
+        core::iter::successors(Some(*self), Element::prev_sibling).count()
+    }
+
+    fn is_last(&self) -> bool {
+        core::iter::successors(self.next_sibling(), NodeRef::next_sibling)
+            .all(|node| !node.is_element())
+    }
+}
+
+fn matches_from<'a, E: Element<'a>>(complex: &Complex<'_>, i: usize, element: E) -> bool {
+    let (combinator, compound) = &complex[i];
+    if !matches_compound(compound, element) {
+        return false;
+    }
+    if i == 0 {
+        return true;
+    }
+    match combinator {
+        Combinator::Child => element
+            .parent()
+            .is_some_and(|parent| matches_from(complex, i - 1, parent)),
+        Combinator::Descendant => core::iter::successors(element.parent(), E::parent)
+            .any(|ancestor| matches_from(complex, i - 1, ancestor)),
+        Combinator::NextSibling => element
+            .prev_sibling()
+            .is_some_and(|sibling| matches_from(complex, i - 1, sibling)),
+        Combinator::SubsequentSibling => {
+            core::iter::successors(element.prev_sibling(), E::prev_sibling)
+                .any(|sibling| matches_from(complex, i - 1, sibling))
+        }
+    }
+}
+
+fn matches_compound<'a>(compound: &Compound<'_>, element: impl Element<'a>) -> bool {
+    if compound.name.is_some_and(|name| name != element.name()) {
+        return false;
+    }
+    compound.filters.iter().all(|filter| match *filter {
+        Filter::Attr { name, op, value } => {
+            let Some(attr) = element.attr(name) else {
+                return false;
+            };
+            match op {
+                AttrOp::Exists => true,
+                AttrOp::Equals => attr == value,
+                AttrOp::Includes => attr.split_ascii_whitespace().any(|token| token == value),
+                AttrOp::DashMatch => {
+                    attr == value
+                        || attr
+                            .strip_prefix(value)
+                            .is_some_and(|rest| rest.starts_with('-'))
+                }
+                AttrOp::Prefix => !value.is_empty() && attr.starts_with(value),
+                AttrOp::Suffix => !value.is_empty() && attr.ends_with(value),
+                AttrOp::Substring => !value.is_empty() && attr.contains(value),
+            }
+        }
+        Filter::NthChild { a, b } => {
+            let index = element.index() as i64;
+            match a {
+                0 => index == b,
+                // index = a * n + b for some n >= 0
+                a => (index - b) % a == 0 && (index - b) / a >= 0,
+            }
+        }
+        Filter::LastChild => element.is_last(),
+    })
+}
+
+/// Element matched by a [`Stream`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamMatch<'a> {
+    pub name: &'a str,
+    /// Raw source of the whole element
+    pub outer_xml: &'a str,
+}
+
+const NONE: usize = usize::MAX;
+
+struct Info<'a> {
+    name: &'a str,
+    attrs: Range<usize>,
+    parent: usize,
+    index: usize,
+}
+
+/// Element of a [`Stream`], only its ancestors and the previous siblings the selector looks
+/// at are kept around
+#[derive(Clone, Copy)]
+struct StreamElement<'s, 'a> {
+    elements: &'s [Info<'a>],
+    attrs: &'s [(&'a str, Option<&'a str>)],
+    i: usize,
+}
+
+impl<'a> StreamElement<'_, 'a> {
+    fn info(&self) -> &Info<'a> {
+        &self.elements[self.i]
+    }
+}
+
+impl<'a> Element<'a> for StreamElement<'_, 'a> {
+    fn name(&self) -> &'a str {
+        self.info().name
+    }
+
+    fn attr(&self, name: &str) -> Option<&'a str> {
+        self.attrs[self.info().attrs.clone()]
+            .iter()
+            .find(|(attr, _)| *attr == name)
+            .map(|(_, value)| value.unwrap_or(""))
+    }
+
+    fn parent(&self) -> Option<Self> {
+        let parent = self.info().parent;
+        (parent != NONE).then_some(Self { i: parent, ..*self })
+    }
+
+    fn prev_sibling(&self) -> Option<Self> {
+        // descendants of closed elements are dropped, so it comes right before
+        let i = self.i.checked_sub(1)?;
+        (self.elements[i].parent == self.info().parent).then_some(Self { i, ..*self })
+    }
+
+    fn index(&self) -> usize {
+        self.info().index
+    }
+
+    fn is_last(&self) -> bool {
+        unreachable!("not streamable")
+    }
+}
+
+/// Runs a [`Selector`] over the events of an [`XmlIter`], see [`Selector::stream`]
+///
+/// ```rust
+/// # use xml1::{css::Selector, XmlIter};
+/// let src = "<ui><button enabled/><button/><label/></ui>";
+/// let selector = Selector::parse("[enabled] ~ *").unwrap();
+/// let names: Vec<_> = selector.stream(XmlIter::from(src)).unwrap().map(|m| m.name).collect();
+/// assert_eq!(names, ["button", "label"]);
+/// ```
+pub struct Stream<'x, 'a> {
+    selector: &'x Selector<'x>,
+    iter: XmlIter<'a>,
+    /// Previous siblings kept for each element, see [`Selector::prev_siblings`]
+    siblings: Option<usize>,
+    /// Open elements and the previous siblings of each of them
+    elements: Vec<Info<'a>>,
+    attrs: Vec<(&'a str, Option<&'a str>)>,
+    /// Indices in `elements` of the open elements
+    open: Vec<usize>,
+    /// Name and start of an element whose attributes are still being read
+    pending: Option<(&'a str, usize)>,
+    queue: VecDeque<StreamMatch<'a>>,
+}
+
+impl<'a> Iterator for Stream<'_, 'a> {
+    type Item = StreamMatch<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(found) = self.queue.pop_front() {
+                return Some(found);
+            }
+
+            let evn = self.iter.next()?;
+            if let XmlEvent::StartTagEnd { .. } = evn {
+                continue;
+            }
+            if let XmlEvent::Attr { name, value } = evn {
+                self.attrs.push((name, value));
+                continue;
+            }
+            if let Some((name, start)) = self.pending.take() {
+                if !self.start_tag_end(name, start) && evn == (XmlEvent::PopElement { name: None })
+                {
+                    continue;
+                }
+            }
+
+            match evn {
+                XmlEvent::PushElement { name } => self.pending = Some((name, self.iter.start)),
+                XmlEvent::PopElement { .. } => {
+                    // stray end tags are ignored
+                    if let Some(i) = self.open.pop() {
+                        self.attrs.truncate(self.elements[i].attrs.end);
+                        self.elements.truncate(i + 1);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl<'a> Stream<'_, 'a> {
+    /// Matches the element that just had all its attributes read, returns `false` for
+    /// declarations, that are never opened
+    fn start_tag_end(&mut self, name: &'a str, start: usize) -> bool {
+        let parent = self.open.last().copied().unwrap_or(NONE);
+        let index = match self.elements.last() {
+            Some(prev) if prev.parent == parent => prev.index + 1,
+            _ => 1,
+        };
+        if let Some(kept) = self.siblings {
+            self.drop_sibling(parent, kept);
+        }
+        let attrs = self.elements.last().map_or(0, |prev| prev.attrs.end)..self.attrs.len();
+        let i = self.elements.len();
+        self.elements.push(Info {
+            name,
+            attrs,
+            parent,
+            index,
+        });
+
+        let element = StreamElement {
+            elements: &self.elements,
+            attrs: &self.attrs,
+            i,
+        };
+        if self.selector.matches_element(element) {
+            let src = self.iter.input.source();
+            let end = match scan::element_bounds(src.as_bytes(), start + 1) {
+                Ok(bounds) | Err(bounds) => bounds.end,
+            };
+            self.queue.push_back(StreamMatch {
+                name,
+                outer_xml: &src[start..end],
+            });
+        }
+
+        if name.starts_with('?') || name.starts_with('!') {
+            return false;
+        }
+        self.open.push(i);
+        true
+    }
+
+    /// Drops the oldest previous sibling of the next child of `parent` when there are more
+    /// than `kept`, so a flat list of elements isn't kept around
+    fn drop_sibling(&mut self, parent: usize, kept: usize) {
+        let siblings = self
+            .elements
+            .iter()
+            .rev()
+            .take(kept + 1)
+            .take_while(|prev| prev.parent == parent)
+            .count();
+        if siblings <= kept {
+            return;
+        }
+        // closed siblings don't have descendants left, so no index of an open element changes
+        let first = self.elements.len() - siblings;
+        let dropped = self.elements.remove(first).attrs;
+        let len = dropped.len();
+        self.attrs.drain(dropped);
+        for info in &mut self.elements[first..] {
+            info.attrs = info.attrs.start - len..info.attrs.end - len;
+        }
+    }
+}
+
+struct Parser<'s> {
+    src: &'s str,
+    pos: usize,
+}
+
+impl<'s> Parser<'s> {
+    fn error(&self, message: &'static str) -> SelectorError {
+        SelectorError {
+            position: self.pos,
+            message,
+        }
+    }
+
+    fn rest(&self) -> &'s str {
+        &self.src[self.pos..]
+    }
+
+    /// Skips whitespace, returns `true` if there was any
+    fn ws(&mut self) -> bool {
+        let rest = self.rest();
+        let len = rest.len() - rest.trim_start().len();
+        self.pos += len;
+        len > 0
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str, message: &'static str) -> Result<(), SelectorError> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error(message))
+        }
+    }
+
+    /// Element and attribute names can have `:` for namespaces, unless followed by a pseudo class
+    fn name(&mut self, colon: bool) -> Option<&'s str> {
+        let rest = self.rest();
+        let mut end = 0;
+        for (i, ch) in rest.char_indices() {
+            let valid = ch.is_alphanumeric()
+                || matches!(ch, '-' | '_')
+                || !ch.is_ascii()
+                || (colon && ch == ':' && i > 0 && !is_pseudo_class(&rest[i + 1..]));
+            if !valid {
+                break;
+            }
+            end = i + ch.len_utf8();
+        }
+        if end == 0 {
+            return None;
+        }
+        self.pos += end;
+        Some(&rest[..end])
+    }
+
+    fn complex(&mut self) -> Result<Complex<'s>, SelectorError> {
+        self.ws();
+        let mut complex = vec![(Combinator::Descendant, self.compound()?)];
+        loop {
+            let space = self.ws();
+            let combinator = if self.eat(">") {
+                Combinator::Child
+            } else if self.eat("+") {
+                Combinator::NextSibling
+            } else if self.eat("~") {
+                Combinator::SubsequentSibling
+            } else if space && !self.rest().is_empty() && !self.rest().starts_with(',') {
+                Combinator::Descendant
+            } else {
+                return Ok(complex);
+            };
+            self.ws();
+            complex.push((combinator, self.compound()?));
+        }
+    }
+
+    fn compound(&mut self) -> Result<Compound<'s>, SelectorError> {
+        let start = self.pos;
+        let name = if self.eat("*") { None } else { self.name(true) };
+        let mut filters = Vec::new();
+        loop {
+            if self.eat("#") {
+                let value = self.name(false).ok_or(self.error("expected id"))?;
+                filters.push(Filter::Attr {
+                    name: "id",
+                    op: AttrOp::Equals,
+                    value,
+                });
+            } else if self.eat(".") {
+                let value = self.name(false).ok_or(self.error("expected class"))?;
+                filters.push(Filter::Attr {
+                    name: "class",
+                    op: AttrOp::Includes,
+                    value,
+                });
+            } else if self.eat("[") {
+                filters.push(self.attr()?);
+            } else if self.eat(":") {
+                filters.push(self.pseudo_class()?);
+            } else {
+                break;
+            }
+        }
+        if self.pos == start {
+            return Err(self.error("expected selector"));
+        }
+        Ok(Compound { name, filters })
+    }
+
+    fn attr(&mut self) -> Result<Filter<'s>, SelectorError> {
+        self.ws();
+        let name = self
+            .name(true)
+            .ok_or(self.error("expected attribute name"))?;
+        self.ws();
+        let op = [
+            ("=", AttrOp::Equals),
+            ("~=", AttrOp::Includes),
+            ("|=", AttrOp::DashMatch),
+            ("^=", AttrOp::Prefix),
+            ("$=", AttrOp::Suffix),
+            ("*=", AttrOp::Substring),
+        ]
+        .into_iter()
+        .find(|(token, _)| self.eat(token))
+        .map(|(_, op)| op);
+
+        let (op, value) = match op {
+            None => (AttrOp::Exists, ""),
+            Some(op) => {
+                self.ws();
+                let rest = self.rest();
+                let value = match rest.chars().next() {
+                    Some(quote @ ('"' | '\'')) => {
+                        let end = rest[1..]
+                            .find(quote)
+                            .ok_or(self.error("unterminated string"))?;
+                        self.pos += end + 2;
+                        &rest[1..end + 1]
+                    }
+                    _ => self
+                        .name(false)
+                        .ok_or(self.error("expected attribute value"))?,
+                };
+                (op, value)
+            }
+        };
+        self.ws();
+        self.expect("]", "expected `]`")?;
+        Ok(Filter::Attr { name, op, value })
+    }
+
+    fn pseudo_class(&mut self) -> Result<Filter<'s>, SelectorError> {
+        if self.eat("first-child") {
+            Ok(Filter::NthChild { a: 0, b: 1 })
+        } else if self.eat("last-child") {
+            Ok(Filter::LastChild)
+        } else if self.eat("nth-child(") {
+            self.ws();
+            let start = self.pos;
+            let end = self.rest().find(')').ok_or(self.error("expected `)`"))?;
+            let (a, b) = nth(self.rest()[..end].trim()).ok_or(SelectorError {
+                position: start,
+                message: "invalid an+b",
+            })?;
+            self.pos += end + 1;
+            Ok(Filter::NthChild { a, b })
+        } else {
+            Err(self.error("unknown pseudo class"))
+        }
+    }
+}
+
+fn is_pseudo_class(s: &str) -> bool {
+    ["first-child", "last-child", "nth-child("]
+        .iter()
+        .any(|pseudo| s.starts_with(pseudo))
+}
+
+/// Parses the `an+b` argument of `:nth-child`
+fn nth(s: &str) -> Option<(i64, i64)> {
+    match s {
+        "odd" => return Some((2, 1)),
+        "even" => return Some((2, 0)),
+        _ => {}
+    }
+    let s: alloc::string::String = s.chars().filter(|ch| !ch.is_whitespace()).collect();
+    let Some((a, b)) = s.split_once('n') else {
+        return Some((0, s.parse().ok()?));
+    };
+    let a = match a {
+        "" | "+" => 1,
+        "-" => -1,
+        a => a.parse().ok()?,
+    };
+    let b = match b {
+        "" => 0,
+        b if b.starts_with('+') || b.starts_with('-') => {
+            b.strip_prefix('+').unwrap_or(b).parse().ok()?
+        }
+        _ => return None,
+    };
+    Some((a, b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn select(selector: &str, src: &str) -> Vec<usize> {
+        let doc = Document::parse(src);
+        let selector = Selector::parse(selector).unwrap();
+        let selected: Vec<_> = selector.select(&doc).iter().map(|n| n.id()).collect();
+        if selector.is_streamable() {
+            // ids follow the order of the start tags
+            let streamed: Vec<_> = selector
+                .stream(XmlIter::from(src))
+                .unwrap()
+                .map(|found| {
+                    doc.nodes()
+                        .find(|n| n.name().is_some_and(|name| core::ptr::eq(name, found.name)))
+                        .unwrap()
+                        .id()
+                })
+                .collect();
+            assert_eq!(selected, streamed);
+        }
+        selected
+    }
+
+    #[test]
+    fn selectors() {
+        let src = "<ui>\
+            <button id=\"ok\" class=\"big primary\" enabled><label lang=\"en-US\"/></button>\
+            <button class=\"primary-ish\"><label lang=\"fr\"/></button>\
+            <panel><button class=\"primary\"/><label/></panel>\
+        </ui>";
+        // ids: ui 1, button 2, label 3, button 4, label 5, panel 6, button 7, label 8
+        assert_eq!(select("button", src), [2, 4, 7]);
+        assert_eq!(select("*", src), [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(select(".primary", src), [2, 7]);
+        assert_eq!(select("#ok", src), [2]);
+        assert_eq!(select("[enabled]", src), [2]);
+        assert_eq!(select("button.primary > label", src), [3]);
+        assert_eq!(select("ui label", src), [3, 5, 8]);
+        assert_eq!(select("ui > label", src), [] as [usize; 0]);
+        assert_eq!(select("button + button", src), [4]);
+        assert_eq!(select("button ~ *", src), [4, 6, 8]);
+        assert_eq!(select("[lang|=en]", src), [3]);
+        assert_eq!(select("[class^='primary']", src), [4, 7]);
+        assert_eq!(select("[class$=ish]", src), [4]);
+        assert_eq!(select("[class*=mary]", src), [2, 4, 7]);
+        assert_eq!(select("[class=primary]", src), [7]);
+        assert_eq!(select("ui > :nth-child(odd)", src), [2, 6]);
+        assert_eq!(select("ui > :nth-child(2n)", src), [4]);
+        assert_eq!(select(":nth-child(-n+1)", src), [1, 2, 3, 5, 7]);
+        assert_eq!(select("ui > :first-child", src), [2]);
+        assert_eq!(select(":last-child", src), [1, 3, 5, 6, 8]);
+        assert_eq!(select("panel button, #ok", src), [2, 7]);
+    }
+
+    #[test]
+    fn namespaces_and_declarations() {
+        let src = "<?xml version=\"1.0\"?><svg><sodipodi:namedview/><inkscape:path/></svg>";
+        assert_eq!(select("sodipodi:namedview", src), [3]);
+        assert_eq!(select("svg > inkscape:path:nth-child(2)", src), [4]);
+        assert_eq!(select("svg", src), [2]);
+    }
+
+    #[test]
+    fn errors() {
+        for (selector, position) in [
+            ("", 0),
+            ("a >", 3),
+            ("a[", 2),
+            ("a[x='y]", 4),
+            ("a.b:hover", 4),
+            ("a:nth-child(x)", 12),
+            ("a, ", 3),
+            ("a )", 2),
+        ] {
+            let error = Selector::parse(selector).unwrap_err();
+            assert_eq!(error.position, position, "{}", selector);
+        }
+        let selector = Selector::parse("a:last-child").unwrap();
+        assert!(selector.stream(XmlIter::from("<a/>")).is_err());
+    }
+
+    #[test]
+    fn stream_like_dom() {
+        let src = include_str!("../benches/files/medium.svg");
+        for selector in [
+            "g path",
+            "defs > linearGradient:nth-child(3n+1) stop",
+            "stop + stop",
+            "filter > feGaussianBlur ~ feComposite",
+            "[inkscape:collect=always]",
+            "svg > *",
+        ] {
+            assert!(!select(selector, src).is_empty(), "{}", selector);
+        }
+    }
+
+    #[test]
+    fn stream_drops_siblings() {
+        let src = std::format!("<r>{}</r>", "<a x=\"1\"><b/></a>".repeat(100));
+        for (selector, kept, found) in [
+            ("a > b", 1, 100),
+            ("a:nth-child(50)", 1, 1),
+            ("a[x] + a", 2, 99),
+            ("a + a + a b", 3, 98),
+            ("a ~ a", 100, 99),
+        ] {
+            let selector = Selector::parse(selector).unwrap();
+            let mut stream = selector.stream(XmlIter::from(src.as_str())).unwrap();
+            let mut count = 0;
+            let mut max = 0;
+            while stream.next().is_some() {
+                count += 1;
+                let children = stream.elements.iter().filter(|info| info.parent == 0);
+                max = max.max(children.count());
+                let with_attrs = stream.elements.iter().filter(|info| info.name == "a");
+                assert_eq!(stream.attrs.len(), with_attrs.count());
+            }
+            assert_eq!((count, max), (found, kept), "{:?}", selector);
+        }
+    }
+}
@@ -0,0 +1,438 @@
+//! Lossless concrete syntax tree, for tools that edit documents without disturbing them.
+//!
+//! Every byte of the source belongs to some node, whitespace, comments and quotes
+//! included, so printing an unmodified tree gives back the source byte for byte.
+//! Edits only touch the pieces they change, everything else is printed as it was.
+//!
+//! ```rust
+//! # use xml1::cst::Cst;
+//! let src = "<ui>\n  <button  label=\"Ok\" /> <!-- keep me -->\n</ui>\n";
+//! let mut cst = Cst::parse(src);
+//! let button = cst.find("button").next().unwrap();
+//! cst.rename(button, "push-button");
+//! cst.set_attr(button, "label", "Cancel");
+//! assert_eq!(
+//!     cst.to_string(),
+//!     "<ui>\n  <push-button  label=\"Cancel\" /> <!-- keep me -->\n</ui>\n"
+//! );
+//! ```
+
+use alloc::{borrow::Cow, string::String, vec::Vec};
+use core::fmt::{self, Write};
+
+use crate::{scan, writer::write_escaped, XmlEvent, XmlIter};
+
+pub type NodeId = usize;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Node<'a> {
+    Element(Element<'a>),
+    /// Text as returned by the parser
+    Text(Cow<'a, str>),
+    /// Whitespace, comments and anything else between elements and texts
+    Trivia(Cow<'a, str>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Element<'a> {
+    pub parent: Option<NodeId>,
+    pub name: Cow<'a, str>,
+    pub attrs: Vec<Attribute<'a>>,
+    /// Whitespace and comments after the last attribute up to and including the `>` or `/>`
+    pub tag_end: Cow<'a, str>,
+    pub children: Vec<NodeId>,
+    /// `None` for self closing and unclosed elements
+    pub end_tag: Option<EndTag<'a>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attribute<'a> {
+    /// Whitespace and comments before the name
+    pub leading: Cow<'a, str>,
+    pub name: Cow<'a, str>,
+    /// Everything between the name and the value, like `=\"`, empty when there is no value
+    pub eq: Cow<'a, str>,
+    /// Raw value, without quotes
+    pub value: Option<Cow<'a, str>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EndTag<'a> {
+    pub name: Cow<'a, str>,
+    /// Whitespace and the `>` after the name
+    pub trailing: Cow<'a, str>,
+}
+
+impl<'a> Element<'a> {
+    pub fn attr(&self, name: &str) -> Option<&Attribute<'a>> {
+        self.attrs.iter().find(|attr| attr.name == name)
+    }
+
+    pub fn is_self_closing(&self) -> bool {
+        self.tag_end.ends_with("/>")
+    }
+}
+
+/// Lossless tree of a document, see the [module docs](self)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cst<'a> {
+    nodes: Vec<Node<'a>>,
+    roots: Vec<NodeId>,
+}
+
+impl<'a> Cst<'a> {
+    pub fn parse(src: &'a str) -> Self {
+        let mut cst = Self {
+            nodes: Vec::new(),
+            roots: Vec::new(),
+        };
+        cst.roots = cst.build(src, None);
+        cst
+    }
+
+    /// Top level nodes
+    #[inline(always)]
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    #[inline(always)]
+    pub fn node(&self, id: NodeId) -> &Node<'a> {
+        &self.nodes[id]
+    }
+
+    #[inline(always)]
+    pub fn node_mut(&mut self, id: NodeId) -> &mut Node<'a> {
+        &mut self.nodes[id]
+    }
+
+    pub fn element(&self, id: NodeId) -> Option<&Element<'a>> {
+        match &self.nodes[id] {
+            Node::Element(element) => Some(element),
+            _ => None,
+        }
+    }
+
+    pub fn element_mut(&mut self, id: NodeId) -> Option<&mut Element<'a>> {
+        match &mut self.nodes[id] {
+            Node::Element(element) => Some(element),
+            _ => None,
+        }
+    }
+
+    /// All elements in document order
+    pub fn elements(&self) -> impl Iterator<Item = NodeId> + '_ {
+        let mut stack: Vec<NodeId> = self.roots.iter().rev().copied().collect();
+        core::iter::from_fn(move || loop {
+            let id = stack.pop()?;
+            if let Node::Element(element) = &self.nodes[id] {
+                stack.extend(element.children.iter().rev());
+                return Some(id);
+            }
+        })
+    }
+
+    /// Elements named `name` in document order
+    pub fn find<'s>(&'s self, name: &'s str) -> impl Iterator<Item = NodeId> + 's {
+        self.elements()
+            .filter(move |&id| matches!(&self.nodes[id], Node::Element(e) if e.name == name))
+    }
+
+    /// Renames the element, including its end tag
+    pub fn rename(&mut self, id: NodeId, name: impl Into<Cow<'a, str>>) {
+        let element = self.element_mut(id).expect("not an element");
+        element.name = name.into();
+        if let Some(end_tag) = &mut element.end_tag {
+            end_tag.name = element.name.clone();
+        }
+    }
+
+    /// Sets the raw value of an attribute, adding it after the last one when missing.
+    /// Any `'"'` in `value` that isn't escaped gets a backslash.
+    pub fn set_attr(&mut self, id: NodeId, name: &str, value: &str) {
+        let mut escaped = String::with_capacity(value.len());
+        write_escaped(&mut escaped, value, b'"').unwrap();
+        let value = Cow::Owned(escaped);
+
+        let element = self.element_mut(id).expect("not an element");
+        match element.attrs.iter_mut().find(|attr| attr.name == name) {
+            Some(attr) => {
+                if attr.value.is_none() {
+                    attr.eq = Cow::Borrowed("=\"");
+                }
+                attr.value = Some(value);
+            }
+            None => element.attrs.push(Attribute {
+                leading: Cow::Borrowed(" "),
+                name: Cow::Owned(name.into()),
+                eq: Cow::Borrowed("=\""),
+                value: Some(value),
+            }),
+        }
+    }
+
+    /// Removes an attribute with its leading whitespace, returns it if found
+    pub fn remove_attr(&mut self, id: NodeId, name: &str) -> Option<Attribute<'a>> {
+        let element = self.element_mut(id).expect("not an element");
+        let i = element.attrs.iter().position(|attr| attr.name == name)?;
+        Some(element.attrs.remove(i))
+    }
+
+    /// Parses `markup` and inserts its nodes as children of `parent` starting at `index`,
+    /// returns the ids of the inserted top level nodes.
+    ///
+    /// A self closing `parent` gets an end tag.
+    pub fn insert_child(&mut self, parent: NodeId, index: usize, markup: &'a str) -> Vec<NodeId> {
+        let ids = self.build(markup, Some(parent));
+        let element = self.element_mut(parent).expect("not an element");
+        if element.end_tag.is_none() && element.is_self_closing() {
+            let tag_end = element.tag_end.trim_end_matches("/>");
+            let tag_end = tag_end.trim_end();
+            element.tag_end = Cow::Owned([tag_end, ">"].concat());
+            element.end_tag = Some(EndTag {
+                name: element.name.clone(),
+                trailing: Cow::Borrowed(">"),
+            });
+        }
+        element.children.splice(index..index, ids.iter().copied());
+        ids
+    }
+
+    /// Builds the nodes of `src`, returns the ids of the top level ones
+    fn build(&mut self, src: &'a str, parent: Option<NodeId>) -> Vec<NodeId> {
+        let bytes = src.as_bytes();
+        let offset = |s: &str| s.as_ptr() as usize - src.as_ptr() as usize;
+
+        let mut top = Vec::new();
+        // open elements
+        let mut stack: Vec<NodeId> = Vec::new();
+        // end of the last piece of source that was given to a node
+        let mut prev = 0;
+
+        let mut iter = XmlIter::from(src);
+        let mut next = iter.next();
+        while let Some(evn) = next {
+            let after = iter.input.offset_from_source_str();
+            next = iter.next();
+
+            match evn {
+                XmlEvent::PushElement { name } => {
+                    let start = offset(name) - 1;
+                    self.trivia(&mut top, &stack, &src[prev..start]);
+                    prev = start + 1 + name.len();
+
+                    let mut attrs = Vec::new();
+                    while let Some(XmlEvent::Attr { name, value }) = next {
+                        let name_start = offset(name);
+                        let name_end = name_start + name.len();
+                        let leading = &src[prev..name_start];
+                        prev = name_end;
+                        let (eq, value) = match value {
+                            Some(value) => {
+                                let value_start = offset(value);
+                                prev = value_start + value.len() + 1;
+                                (&src[name_end..value_start], Some(Cow::Borrowed(value)))
+                            }
+                            None => ("", None),
+                        };
+                        attrs.push(Attribute {
+                            leading: Cow::Borrowed(leading),
+                            name: Cow::Borrowed(name),
+                            eq: Cow::Borrowed(eq),
+                            value,
+                        });
+                        next = iter.next();
+                    }
+
+                    let (end, self_closing) =
+                        scan::tag_end(bytes, prev).unwrap_or((src.len(), false));
+                    let tag_end = &src[prev..end];
+                    prev = end;
+                    if self_closing {
+                        // the matching `PopElement { name: None }`
+                        next = iter.next();
+                    }
+
+                    let id = self.push(
+                        &mut top,
+                        &stack,
+                        Node::Element(Element {
+                            parent: stack.last().copied().or(parent),
+                            name: Cow::Borrowed(name),
+                            attrs,
+                            tag_end: Cow::Borrowed(tag_end),
+                            children: Vec::new(),
+                            end_tag: None,
+                        }),
+                    );
+                    if !self_closing {
+                        stack.push(id);
+                    }
+                }
+                XmlEvent::PopElement { name: Some(name) } => {
+                    // "</" comes right before the name
+                    let start = offset(name) - 2;
+                    self.trivia(&mut top, &stack, &src[prev..start]);
+                    match stack.pop() {
+                        Some(id) => {
+                            let Node::Element(element) = &mut self.nodes[id] else {
+                                unreachable!()
+                            };
+                            element.end_tag = Some(EndTag {
+                                name: Cow::Borrowed(name),
+                                trailing: Cow::Borrowed(&src[start + 2 + name.len()..after]),
+                            });
+                        }
+                        // stray end tag
+                        None => self.trivia(&mut top, &stack, &src[start..after]),
+                    }
+                    prev = after;
+                }
+                XmlEvent::Text { text } => {
+                    let start = offset(text);
+                    self.trivia(&mut top, &stack, &src[prev..start]);
+                    self.push(&mut top, &stack, Node::Text(Cow::Borrowed(text)));
+                    prev = start + text.len();
+                }
+                XmlEvent::PopElement { name: None }
+                | XmlEvent::Attr { .. }
+                | XmlEvent::Comment { .. }
+                | XmlEvent::StartTagEnd { .. } => {
+                    unreachable!("handled with the start tag, comments are trivia")
+                }
+            }
+        }
+        self.trivia(&mut top, &stack, &src[prev..]);
+
+        for &id in &top {
+            if let Node::Element(element) = &mut self.nodes[id] {
+                element.parent = parent;
+            }
+        }
+        top
+    }
+
+    fn push(&mut self, top: &mut Vec<NodeId>, stack: &[NodeId], node: Node<'a>) -> NodeId {
+        let id = self.nodes.len();
+        self.nodes.push(node);
+        match stack.last() {
+            Some(&parent) => match &mut self.nodes[parent] {
+                Node::Element(element) => element.children.push(id),
+                _ => unreachable!(),
+            },
+            None => top.push(id),
+        }
+        id
+    }
+
+    fn trivia(&mut self, top: &mut Vec<NodeId>, stack: &[NodeId], trivia: &'a str) {
+        if !trivia.is_empty() {
+            self.push(top, stack, Node::Trivia(Cow::Borrowed(trivia)));
+        }
+    }
+
+    fn write_node(&self, out: &mut impl Write, id: NodeId) -> fmt::Result {
+        match &self.nodes[id] {
+            Node::Text(text) | Node::Trivia(text) => out.write_str(text),
+            Node::Element(element) => {
+                out.write_char('<')?;
+                out.write_str(&element.name)?;
+                for attr in &element.attrs {
+                    out.write_str(&attr.leading)?;
+                    out.write_str(&attr.name)?;
+                    if let Some(value) = &attr.value {
+                        out.write_str(&attr.eq)?;
+                        out.write_str(value)?;
+                        out.write_char('"')?;
+                    }
+                }
+                out.write_str(&element.tag_end)?;
+                for &child in &element.children {
+                    self.write_node(out, child)?;
+                }
+                if let Some(end_tag) = &element.end_tag {
+                    out.write_str("</")?;
+                    out.write_str(&end_tag.name)?;
+                    out.write_str(&end_tag.trailing)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for Cst<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for &id in &self.roots {
+            self.write_node(f, id)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::string::ToString;
+
+    use super::*;
+
+    #[test]
+    fn lossless() {
+        for src in [
+            include_str!("../benches/files/fonts.conf"),
+            include_str!("../benches/files/huge.xml"),
+            include_str!("../benches/files/large.plist"),
+            include_str!("../benches/files/medium.svg"),
+            "",
+            "  text\n only  ",
+            "<r <!-- a --> x = \"1\"  y <!-- b --> />",
+            "</stray> <a><b>unclosed",
+            r#"<r v="\"q\"">C:\ text </r  >"#,
+        ] {
+            assert_eq!(Cst::parse(src).to_string(), src);
+        }
+    }
+
+    #[test]
+    fn structure() {
+        let cst = Cst::parse("<r>\n  <a x=\"1\"/>\n  text\n</r>");
+        assert_eq!(cst.roots().len(), 1);
+        let r = cst.element(0).unwrap();
+        assert_eq!(r.name, "r");
+        assert_eq!(r.children.len(), 5);
+        let a = cst.find("a").next().unwrap();
+        let a = cst.element(a).unwrap();
+        assert_eq!(a.parent, Some(0));
+        assert!(a.is_self_closing());
+        assert_eq!(a.attr("x").unwrap().value.as_deref(), Some("1"));
+        assert!(matches!(cst.node(r.children[3]), Node::Text(text) if text == "text"));
+    }
+
+    #[test]
+    fn edits() {
+        let src =
+            "<ui>\n  <w  color = \"red\" <!-- c --> flag/>\n  <w>\n    <x/>\n  </w >\n</ui>\n";
+        let mut cst = Cst::parse(src);
+        let ws: Vec<_> = cst.find("w").collect();
+
+        cst.rename(ws[1], "widget");
+        cst.set_attr(ws[0], "color", "say \"blue\"");
+        cst.set_attr(ws[0], "flag", "1");
+        cst.set_attr(ws[0], "new", "2");
+        assert_eq!(
+            cst.to_string(),
+            "<ui>\n  <w  color = \"say \\\"blue\\\"\" <!-- c --> flag=\"1\" new=\"2\"/>\n  <widget>\n    <x/>\n  </widget >\n</ui>\n"
+        );
+
+        cst.remove_attr(ws[0], "color");
+        cst.insert_child(ws[0], 0, "<y/>");
+        cst.insert_child(ws[1], 0, "\n    <z a=\"1\"/>");
+        assert_eq!(
+            cst.to_string(),
+            "<ui>\n  <w <!-- c --> flag=\"1\" new=\"2\"><y/></w>\n  <widget>\n    <z a=\"1\"/>\n    <x/>\n  </widget >\n</ui>\n"
+        );
+
+        let z = cst.find("z").next().unwrap();
+        assert_eq!(cst.element(z).unwrap().parent, Some(ws[1]));
+    }
+}
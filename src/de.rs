@@ -0,0 +1,1056 @@
+//! Deserializes documents into Rust types with [`serde`], enabled with the `serde` feature.
+//!
+//! The root element is the deserialized value, its name is ignored:
+//! - attributes are fields named after them with a `@` prefix, see [`Deserializer::with_attr_prefix`],
+//!   valueless attributes like `<r clip>` are `true`
+//! - child elements are fields named after them, repeated children go into a `Vec`,
+//!   even when other elements are between them
+//! - the text of an element is the `$text` field, or the whole value when it only has text
+//! - enums are either a text, for unit variants, or a child element named after the variant
+//!
+//! Strings are borrowed from the source, unless they have escape sequences or multiple lines.
+//! Texts are raw, attribute values follow the parser rules: `\uXXXX` is decoded and any other
+//! escaped char, like `\"`, is kept without its backslash.
+//!
+//! Like [`XmlIter`], malformed markup panics, only documents that parse but don't fit the
+//! type are returned as [`Error`]s.
+//!
+//! ```rust
+//! # use serde::Deserialize;
+//! #[derive(Deserialize)]
+//! struct Font<'a> {
+//!     #[serde(rename = "@family")]
+//!     family: &'a str,
+//!     #[serde(rename = "@hinting", default)]
+//!     hinting: bool,
+//!     size: Vec<f32>,
+//!     #[serde(rename = "$text")]
+//!     note: Option<String>,
+//! }
+//!
+//! let src = r#"<font family="Fira" hinting><size>12</size>"mono"<size>14.5</size></font>"#;
+//! let font: Font = xml1::de::from_str(src).unwrap();
+//! assert_eq!(font.family, "Fira");
+//! assert!(font.hinting);
+//! assert_eq!(font.size, [12.0, 14.5]);
+//! assert_eq!(font.note.as_deref(), Some("\"mono\""));
+//! ```
+
+use alloc::{
+    borrow::Cow,
+    collections::BTreeSet,
+    string::{String, ToString},
+};
+use core::fmt;
+
+use serde::de::{
+    self,
+    value::{BorrowedStrDeserializer, StrDeserializer, StringDeserializer},
+    DeserializeSeed, Unexpected, Visitor,
+};
+
+use crate::{Position, XmlEvent, XmlIter};
+
+/// Field name of the text of an element
+pub const TEXT_KEY: &str = "$text";
+
+/// Error of [`from_str`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    /// Where the parser stopped, `None` for errors that didn't pass through [`from_str`]
+    pub position: Option<Position>,
+    pub message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.position {
+            Some(position) => write!(f, "{} at {}", self.message, position),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+impl de::StdError for Error {}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self {
+            position: None,
+            message: msg.to_string(),
+        }
+    }
+}
+
+/// Deserializes a `T` from the root element of `src`
+///
+/// Panics on syntax errors, like an unclosed attribute value, same as [`XmlIter`], so
+/// untrusted input should be checked before or deserialized inside `catch_unwind`
+pub fn from_str<'de, T: de::Deserialize<'de>>(src: &'de str) -> Result<T, Error> {
+    let mut de = Deserializer::new(src);
+    T::deserialize(&mut de).map_err(|mut err| {
+        err.position
+            .get_or_insert_with(|| de.iter.current_position());
+        err
+    })
+}
+
+/// Maps a document to Rust types, see the [module docs](self)
+pub struct Deserializer<'de> {
+    iter: XmlIter<'de>,
+    attr_prefix: &'static str,
+}
+
+impl<'de> Deserializer<'de> {
+    pub fn new(src: &'de str) -> Self {
+        Self::from(XmlIter::from(src))
+    }
+
+    /// Prefix of the field names of attributes, `@` by default, it may be empty
+    pub fn with_attr_prefix(mut self, prefix: &'static str) -> Self {
+        self.attr_prefix = prefix;
+        self
+    }
+
+    fn error(&self, message: impl fmt::Display) -> Error {
+        Error {
+            position: Some(self.iter.current_position()),
+            message: message.to_string(),
+        }
+    }
+
+    /// Name of the root element, skipping any declaration before it
+    fn root(&mut self) -> Result<&'de str, Error> {
+        loop {
+            match self.iter.next() {
+                Some(XmlEvent::PushElement { name }) => {
+                    if !(name.starts_with('?') || name.starts_with('!')) {
+                        return Ok(name);
+                    }
+                    // declarations are never closed
+                    self.skip_start_tag()?;
+                }
+                Some(_) => {}
+                None => return Err(self.error("missing root element")),
+            }
+        }
+    }
+
+    /// Moves past the start tag of the element just pushed, returns if it was self closing
+    fn skip_start_tag(&mut self) -> Result<bool, Error> {
+        loop {
+            match self.iter.next() {
+                Some(XmlEvent::StartTagEnd { self_closing }) => {
+                    if self_closing {
+                        // `PopElement { name: None }`
+                        self.iter.next();
+                    }
+                    return Ok(self_closing);
+                }
+                Some(_) => {}
+                None => return Err(self.error("unexpected end of file")),
+            }
+        }
+    }
+
+    /// Moves past the end of the current element, from anywhere inside it
+    fn skip_rest(&mut self) {
+        while let Some(evn) = self.iter.next() {
+            match evn {
+                XmlEvent::PushElement { .. } => {
+                    self.iter.skip_element();
+                }
+                XmlEvent::StartTagEnd { self_closing: true } => {
+                    self.iter.next();
+                    return;
+                }
+                XmlEvent::PopElement { .. } => return,
+                _ => {}
+            }
+        }
+    }
+
+    /// Consumes the rest of the content of the current element, which must only have texts
+    fn text(&mut self, name: &str) -> Result<Cow<'de, str>, Error> {
+        let mut text = Cow::Borrowed("");
+        while let Some(evn) = self.iter.next() {
+            match evn {
+                XmlEvent::Text { text: line } => push_line(&mut text, line),
+                XmlEvent::PushElement { name: child } => {
+                    return Err(self.error(format_args!(
+                        "unexpected element `{}` in the text of `{}`",
+                        child, name
+                    )))
+                }
+                XmlEvent::PopElement { .. } => break,
+                _ => {}
+            }
+        }
+        Ok(text)
+    }
+}
+
+impl<'de> From<XmlIter<'de>> for Deserializer<'de> {
+    /// Deserializes from a configured `iter`, like one made with [`XmlIter::from_sub_str`]
+    fn from(iter: XmlIter<'de>) -> Self {
+        Self {
+            iter: iter.with_comments(false).with_start_tag_end(true),
+            attr_prefix: "@",
+        }
+    }
+}
+
+/// Texts are split at new lines by the parser
+fn push_line<'de>(text: &mut Cow<'de, str>, line: &'de str) {
+    if text.is_empty() {
+        *text = Cow::Borrowed(line);
+    } else {
+        let text = text.to_mut();
+        text.push('\n');
+        text.push_str(line);
+    }
+}
+
+/// Decodes the escape sequences of a raw attribute value
+fn unescape(raw: Cow<'_, str>) -> Result<Cow<'_, str>, Error> {
+    if !raw.contains('\\') {
+        return Ok(raw);
+    }
+    let mut out = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            out.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('u') => {
+                let hex = chars.as_str().get(..4).unwrap_or("");
+                let ch = u32::from_str_radix(hex, 16)
+                    .ok()
+                    .filter(|_| hex.len() == 4)
+                    .and_then(char::from_u32);
+                match ch {
+                    Some(ch) => out.push(ch),
+                    None => {
+                        return Err(de::Error::custom(format_args!(
+                            "invalid escape sequence `\\u{}`",
+                            hex
+                        )))
+                    }
+                }
+                chars.nth(3);
+            }
+            Some(ch) => out.push(ch),
+            None => out.push('\\'),
+        }
+    }
+    Ok(Cow::Owned(out))
+}
+
+macro_rules! forward_to_root {
+    ($($method:ident)*) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            let name = self.root()?;
+            ElementDeserializer { de: self, name, field: false }.$method(visitor)
+        }
+    )*};
+}
+
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+
+    forward_to_root! {
+        deserialize_any deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32
+        deserialize_i64 deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+        deserialize_f32 deserialize_f64 deserialize_char deserialize_str deserialize_string
+        deserialize_bytes deserialize_byte_buf deserialize_option deserialize_unit
+        deserialize_seq deserialize_map deserialize_identifier deserialize_ignored_any
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let root = self.root()?;
+        ElementDeserializer {
+            de: self,
+            name: root,
+            field: false,
+        }
+        .deserialize_enum(name, variants, visitor)
+    }
+}
+
+/// Element right after its `PushElement`
+struct ElementDeserializer<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    name: &'de str,
+    /// Field of a parent element, so it makes a sequence with its siblings of the same name
+    field: bool,
+}
+
+impl<'de> ElementDeserializer<'_, 'de> {
+    /// Consumes the element, which must only have texts
+    fn text(self) -> Result<ValueDeserializer<'de>, Error> {
+        let text = if self.de.skip_start_tag()? {
+            Cow::Borrowed("")
+        } else {
+            self.de.text(self.name)?
+        };
+        Ok(ValueDeserializer { value: Some(text) })
+    }
+
+    /// Has attributes or child elements, doesn't consume any input
+    fn is_structured(&self) -> bool {
+        for evn in self.de.iter.clone() {
+            match evn {
+                XmlEvent::Attr { .. } | XmlEvent::PushElement { .. } => return true,
+                XmlEvent::StartTagEnd { self_closing: true } | XmlEvent::PopElement { .. } => {
+                    return false
+                }
+                _ => {}
+            }
+        }
+        false
+    }
+}
+
+macro_rules! forward_to_text {
+    ($($method:ident)*) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            self.text()?.$method(visitor)
+        }
+    )*};
+}
+
+impl<'de> de::Deserializer<'de> for ElementDeserializer<'_, 'de> {
+    type Error = Error;
+
+    forward_to_text! {
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_f32
+        deserialize_f64 deserialize_char deserialize_str deserialize_string deserialize_bytes
+        deserialize_byte_buf deserialize_identifier
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.is_structured() {
+            self.deserialize_map(visitor)
+        } else {
+            self.text()?.deserialize_any(visitor)
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.de.iter.skip_element();
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.field {
+            let mut siblings = Siblings {
+                de: self.de,
+                name: self.name,
+                first: true,
+                after_first: None,
+                done: false,
+            };
+            let value = visitor.visit_seq(&mut siblings)?;
+            // the other siblings are skipped by the parent, which resumes after the first one
+            match siblings.after_first {
+                Some(checkpoint) => siblings.de.iter.restore(checkpoint),
+                None => {
+                    siblings.de.iter.skip_element();
+                }
+            }
+            Ok(value)
+        } else {
+            let done = self.de.skip_start_tag()?;
+            let mut children = Children { de: self.de, done };
+            let value = visitor.visit_seq(&mut children)?;
+            if !children.done {
+                children.de.skip_rest();
+            }
+            Ok(value)
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let mut access = ElementAccess {
+            de: self.de,
+            in_start_tag: true,
+            done: false,
+            seen: BTreeSet::new(),
+            text_seen: false,
+            pending: None,
+            key: String::new(),
+        };
+        let value = visitor.visit_map(&mut access)?;
+        if !access.done {
+            access.de.skip_rest();
+        }
+        Ok(value)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        if self.de.skip_start_tag()? {
+            return ValueDeserializer {
+                value: Some(Cow::Borrowed("")),
+            }
+            .deserialize_enum(name, variants, visitor);
+        }
+
+        // the first child element is the variant
+        let mut iter = self.de.iter.clone();
+        let variant = loop {
+            match iter.next() {
+                Some(XmlEvent::PushElement { name }) => break Some(name),
+                Some(XmlEvent::Text { .. }) | Some(XmlEvent::PopElement { .. }) | None => {
+                    break None
+                }
+                _ => {}
+            }
+        };
+        match variant {
+            Some(name) => {
+                self.de.iter = iter;
+                let value = visitor.visit_enum(VariantElement { de: self.de, name })?;
+                self.de.skip_rest();
+                Ok(value)
+            }
+            None => ValueDeserializer {
+                value: Some(self.de.text(self.name)?),
+            }
+            .deserialize_enum(name, variants, visitor),
+        }
+    }
+}
+
+enum Pending<'de> {
+    Attr(Option<&'de str>),
+    Element(&'de str),
+    Text,
+}
+
+/// Attributes, child elements and text of an element as a map
+struct ElementAccess<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    in_start_tag: bool,
+    done: bool,
+    /// Names of the child elements already returned, repeated ones are read all at once
+    seen: BTreeSet<&'de str>,
+    text_seen: bool,
+    /// Value of the last key
+    pending: Option<Pending<'de>>,
+    /// Prefixed attribute name
+    key: String,
+}
+
+impl<'de> de::MapAccess<'de> for ElementAccess<'_, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        while self.in_start_tag {
+            match self.de.iter.next() {
+                Some(XmlEvent::Attr { name, value }) => {
+                    self.pending = Some(Pending::Attr(value));
+                    if self.de.attr_prefix.is_empty() {
+                        return seed
+                            .deserialize(BorrowedStrDeserializer::new(name))
+                            .map(Some);
+                    }
+                    self.key.clear();
+                    self.key.push_str(self.de.attr_prefix);
+                    self.key.push_str(name);
+                    return seed
+                        .deserialize(StrDeserializer::new(self.key.as_str()))
+                        .map(Some);
+                }
+                Some(XmlEvent::StartTagEnd { self_closing }) => {
+                    self.in_start_tag = false;
+                    if self_closing {
+                        // `PopElement { name: None }`
+                        self.de.iter.next();
+                        self.done = true;
+                    }
+                }
+                Some(_) => {}
+                None => return Err(self.de.error("unexpected end of file")),
+            }
+        }
+
+        while !self.done {
+            let checkpoint = self.de.iter.checkpoint();
+            match self.de.iter.next() {
+                Some(XmlEvent::PushElement { name }) => {
+                    if !self.seen.insert(name) {
+                        self.de.iter.skip_element();
+                        continue;
+                    }
+                    self.pending = Some(Pending::Element(name));
+                    return seed
+                        .deserialize(BorrowedStrDeserializer::new(name))
+                        .map(Some);
+                }
+                Some(XmlEvent::Text { .. }) => {
+                    if self.text_seen {
+                        continue;
+                    }
+                    self.text_seen = true;
+                    self.de.iter.restore(checkpoint);
+                    self.pending = Some(Pending::Text);
+                    return seed
+                        .deserialize(BorrowedStrDeserializer::new(TEXT_KEY))
+                        .map(Some);
+                }
+                // unclosed elements end with the input
+                Some(XmlEvent::PopElement { .. }) | None => self.done = true,
+                Some(_) => {}
+            }
+        }
+        Ok(None)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        match self.pending.take() {
+            Some(Pending::Attr(value)) => seed.deserialize(ValueDeserializer {
+                value: value
+                    .map(|value| unescape(Cow::Borrowed(value)))
+                    .transpose()?,
+            }),
+            Some(Pending::Element(name)) => seed.deserialize(ElementDeserializer {
+                de: self.de,
+                name,
+                field: true,
+            }),
+            Some(Pending::Text) => {
+                // every text of the element, in between child elements too
+                let mut text = Cow::Borrowed("");
+                let mut iter = self.de.iter.clone();
+                while let Some(evn) = iter.next() {
+                    match evn {
+                        XmlEvent::Text { text: line } => push_line(&mut text, line),
+                        XmlEvent::PushElement { .. } => {
+                            iter.skip_element();
+                        }
+                        XmlEvent::PopElement { .. } => break,
+                        _ => {}
+                    }
+                }
+                seed.deserialize(ValueDeserializer { value: Some(text) })
+            }
+            None => Err(de::Error::custom("value without a key")),
+        }
+    }
+}
+
+/// Repeated child elements with the same name
+struct Siblings<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    name: &'de str,
+    /// The first element, that was returned as the key, wasn't read yet
+    first: bool,
+    after_first: Option<crate::Checkpoint>,
+    done: bool,
+}
+
+impl<'de> de::SeqAccess<'de> for Siblings<'_, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        let element = ElementDeserializer {
+            de: self.de,
+            name: self.name,
+            field: false,
+        };
+        if self.first {
+            self.first = false;
+            let value = seed.deserialize(element)?;
+            self.after_first = Some(self.de.iter.checkpoint());
+            return Ok(Some(value));
+        }
+        while !self.done {
+            match self.de.iter.next() {
+                Some(XmlEvent::PushElement { name }) if name == self.name => {
+                    return seed
+                        .deserialize(ElementDeserializer {
+                            de: self.de,
+                            name,
+                            field: false,
+                        })
+                        .map(Some);
+                }
+                Some(XmlEvent::PushElement { .. }) => {
+                    self.de.iter.skip_element();
+                }
+                Some(XmlEvent::PopElement { .. }) | None => self.done = true,
+                Some(_) => {}
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Every child element, of any name
+struct Children<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    done: bool,
+}
+
+impl<'de> de::SeqAccess<'de> for Children<'_, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        while !self.done {
+            match self.de.iter.next() {
+                Some(XmlEvent::PushElement { name }) => {
+                    return seed
+                        .deserialize(ElementDeserializer {
+                            de: self.de,
+                            name,
+                            field: false,
+                        })
+                        .map(Some);
+                }
+                Some(XmlEvent::PopElement { .. }) | None => self.done = true,
+                Some(_) => {}
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Child element named after an enum variant
+struct VariantElement<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    name: &'de str,
+}
+
+impl<'a, 'de> de::EnumAccess<'de> for VariantElement<'a, 'de> {
+    type Error = Error;
+    type Variant = ElementDeserializer<'a, 'de>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), Error> {
+        let variant = seed.deserialize(BorrowedStrDeserializer::new(self.name))?;
+        Ok((
+            variant,
+            ElementDeserializer {
+                de: self.de,
+                name: self.name,
+                field: false,
+            },
+        ))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for ElementDeserializer<'_, 'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        self.de.iter.skip_element();
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
+
+/// Unescaped attribute value or text, `None` for valueless attributes
+struct ValueDeserializer<'de> {
+    value: Option<Cow<'de, str>>,
+}
+
+impl<'de> ValueDeserializer<'de> {
+    fn str(self) -> Result<Cow<'de, str>, Error> {
+        Ok(self.value.unwrap_or(Cow::Borrowed("")))
+    }
+}
+
+macro_rules! parse_value {
+    ($($method:ident $visit:ident)*) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            let s = self.str()?;
+            match s.trim().parse() {
+                Ok(v) => visitor.$visit(v),
+                Err(_) => Err(de::Error::invalid_value(Unexpected::Str(&s), &visitor)),
+            }
+        }
+    )*};
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = Error;
+
+    parse_value! {
+        deserialize_i8 visit_i8 deserialize_i16 visit_i16 deserialize_i32 visit_i32
+        deserialize_i64 visit_i64 deserialize_u8 visit_u8 deserialize_u16 visit_u16
+        deserialize_u32 visit_u32 deserialize_u64 visit_u64 deserialize_f32 visit_f32
+        deserialize_f64 visit_f64 deserialize_char visit_char
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.value.is_none() {
+            return visitor.visit_bool(true);
+        }
+        match self.str()? {
+            Cow::Borrowed(s) => visitor.visit_borrowed_str(s),
+            Cow::Owned(s) => visitor.visit_string(s),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.value.is_none() {
+            return visitor.visit_bool(true);
+        }
+        let s = self.str()?;
+        match s.trim() {
+            "true" | "1" => visitor.visit_bool(true),
+            "false" | "0" => visitor.visit_bool(false),
+            _ => Err(de::Error::invalid_value(Unexpected::Str(&s), &visitor)),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.str()? {
+            Cow::Borrowed(s) => visitor.visit_borrowed_str(s),
+            Cow::Owned(s) => visitor.visit_string(s),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.str()? {
+            Cow::Borrowed(s) => visitor.visit_borrowed_bytes(s.as_bytes()),
+            Cow::Owned(s) => visitor.visit_byte_buf(s.into_bytes()),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.str()? {
+            Cow::Borrowed(s) => visitor.visit_enum(BorrowedStrDeserializer::new(s.trim())),
+            Cow::Owned(s) => visitor.visit_enum(StringDeserializer::new(s.trim().into())),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        i128 u128 seq tuple tuple_struct map struct unit_struct ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, string::String, vec, vec::Vec};
+
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Config<'a> {
+        #[serde(rename = "@version")]
+        version: u32,
+        #[serde(rename = "@debug", default)]
+        debug: bool,
+        #[serde(rename = "@name", borrow)]
+        name: Cow<'a, str>,
+        #[serde(default)]
+        dir: Vec<Dir<'a>>,
+        #[serde(default)]
+        cache: Option<&'a str>,
+        mode: Mode,
+        shape: Shape,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Dir<'a> {
+        #[serde(rename = "@prefix")]
+        prefix: Option<&'a str>,
+        #[serde(rename = "$text")]
+        path: String,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Mode {
+        Fast,
+        Slow,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Shape {
+        Circle {
+            #[serde(rename = "@r")]
+            r: f32,
+        },
+        Point,
+    }
+
+    #[test]
+    fn structs() {
+        let src = r#"<?xml version="1.0"?>
+            <!-- settings -->
+            <config version="2" debug name="a \"b\"">
+                <dir>/usr/share/fonts</dir>
+                <cache>~/.cache</cache>
+                <dir prefix="xdg">fonts
+                    more</dir>
+                <mode> slow </mode>
+                <shape><circle r="1.5"/></shape>
+                <unknown><dir>ignored</dir></unknown>
+                <dir>A\u000A b> C:\temp</dir>
+            </config>"#;
+        let config: Config = from_str(src).unwrap();
+        assert_eq!(
+            config,
+            Config {
+                version: 2,
+                debug: true,
+                name: Cow::Owned(r#"a "b""#.into()),
+                dir: vec![
+                    Dir {
+                        prefix: None,
+                        path: "/usr/share/fonts".into()
+                    },
+                    Dir {
+                        prefix: Some("xdg"),
+                        path: "fonts\nmore".into()
+                    },
+                    Dir {
+                        prefix: None,
+                        path: r"A\u000A b> C:\temp".into()
+                    },
+                ],
+                cache: Some("~/.cache"),
+                mode: Mode::Slow,
+                shape: Shape::Circle { r: 1.5 },
+            }
+        );
+
+        let config: Config =
+            from_str("<c version=\"1\" name=\"x\"><mode>fast</mode><shape><point/></shape></c>")
+                .unwrap();
+        assert_eq!(config.dir, []);
+        assert!(!config.debug);
+        assert!(matches!(config.name, Cow::Borrowed("x")));
+        assert_eq!(config.shape, Shape::Point);
+    }
+
+    #[test]
+    fn attr_prefix() {
+        #[derive(Deserialize)]
+        struct Rect {
+            width: f64,
+            height: f64,
+        }
+        let mut de =
+            Deserializer::new("<rect width=\"2\"><height>3</height></rect>").with_attr_prefix("");
+        let rect = Rect::deserialize(&mut de).unwrap();
+        assert_eq!((rect.width, rect.height), (2.0, 3.0));
+    }
+
+    #[test]
+    fn sequences_and_maps() {
+        let list: Vec<u8> = from_str("<list><a>1</a><b>2</b>text<c>3</c></list>").unwrap();
+        assert_eq!(list, [1, 2, 3]);
+
+        let map: BTreeMap<String, &str> = from_str("<m x=\"1\"><y>2</y><z/></m>").unwrap();
+        assert_eq!(
+            map,
+            BTreeMap::from([("@x".into(), "1"), ("y".into(), "2"), ("z".into(), "")])
+        );
+
+        let (a, b): (String, i32) = from_str("<t><a>x</a><b>-1</b></t>").unwrap();
+        assert_eq!((a.as_str(), b), ("x", -1));
+
+        let text: &str = from_str("<r>only text</r>").unwrap();
+        assert_eq!(text, "only text");
+    }
+
+    #[test]
+    fn errors() {
+        #[derive(Debug, Deserialize)]
+        #[allow(unused)]
+        struct Number {
+            #[serde(rename = "@n")]
+            n: u8,
+        }
+        let err = from_str::<Number>("<r\nn=\"300\"/>").unwrap_err();
+        assert!(err.message.contains("300"), "{}", err);
+        assert!(from_str::<Number>("<r n=\"1\"/>\n").is_ok());
+        let err = from_str::<Number>("<r/>").unwrap_err();
+        assert_eq!(err.message, "missing field `@n`");
+        let err = from_str::<Number>("").unwrap_err();
+        assert_eq!(err.to_string(), "missing root element at 1:1");
+        let err = from_str::<String>("<r>a<b/></r>").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unexpected element `b` in the text of `r` at 1:7"
+        );
+        let err = from_str::<Number>(r#"<r n="\u00"/>"#).unwrap_err();
+        assert!(err.message.contains("invalid escape"), "{}", err);
+    }
+
+    #[test]
+    #[should_panic(expected = "unexpected end of file")]
+    fn syntax_errors_panic() {
+        let _ = from_str::<BTreeMap<String, String>>("<r a=\"1");
+    }
+}
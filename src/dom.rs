@@ -0,0 +1,572 @@
+//! Tree of a document built into a single arena, for consumers that want to navigate
+//! rather than handle events.
+//!
+//! Nodes and attributes are stored in two flat arrays and every name, value and text
+//! borrows from the source. Declarations like `<?xml ...?>` are leaf elements.
+//!
+//! ```rust
+//! # use xml1::dom::Document;
+//! let doc = Document::parse("<?xml version=\"1.0\"?><r><a x=\"1\">text</a><b/></r>");
+//! let root = doc.root().unwrap();
+//! assert_eq!(root.name(), Some("r"));
+//! let a = root.children().next().unwrap();
+//! assert_eq!(a.attr("x"), Some("1"));
+//! assert_eq!(a.text(), Some("text"));
+//! assert_eq!(a.parent(), Some(root));
+//! let names: Vec<_> = root.descendants().filter_map(|n| n.name()).collect();
+//! assert_eq!(names, ["r", "a", "b"]);
+//! ```
+//!
+//! Use an [`OwnedDocument`] to keep a document around without its source.
+
+use alloc::{boxed::Box, vec::Vec};
+use core::{fmt, mem::ManuallyDrop, ops::Range, ptr::NonNull};
+
+use crate::{XmlEvent, XmlIter};
+
+const NONE: u32 = u32::MAX;
+
+pub type NodeId = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind<'a> {
+    /// The node holding the top level nodes, always the first one
+    Document,
+    Element {
+        name: &'a str,
+    },
+    Text {
+        text: &'a str,
+    },
+    /// Only when built from an [`XmlIter`] with comments
+    Comment {
+        text: &'a str,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Attribute<'a> {
+    pub name: &'a str,
+    pub value: Option<&'a str>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Node<'a> {
+    kind: NodeKind<'a>,
+    attrs: Range<u32>,
+    parent: u32,
+    prev_sibling: u32,
+    next_sibling: u32,
+    first_child: u32,
+    last_child: u32,
+}
+
+/// Arena of all nodes of a document, see the [module docs](self)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Document<'a> {
+    src: &'a str,
+    nodes: Vec<Node<'a>>,
+    attrs: Vec<Attribute<'a>>,
+}
+
+impl<'a> Document<'a> {
+    /// Parses the whole `src`, which must have less than 4G nodes
+    pub fn parse(src: &'a str) -> Self {
+        Self::parse_iter(XmlIter::from(src))
+    }
+
+    /// Builds the document from all the events of `iter`, use it to keep comments
+    pub fn parse_iter(iter: XmlIter<'a>) -> Self {
+        let mut doc = Self {
+            src: iter.input.source(),
+            nodes: Vec::new(),
+            attrs: Vec::new(),
+        };
+        doc.nodes.push(Node {
+            kind: NodeKind::Document,
+            attrs: 0..0,
+            parent: NONE,
+            prev_sibling: NONE,
+            next_sibling: NONE,
+            first_child: NONE,
+            last_child: NONE,
+        });
+
+        let mut stack = Vec::new();
+        let mut parent = 0;
+        // declarations are closed by the end of their start tag
+        let mut declaration = false;
+        for evn in iter {
+            if declaration && !matches!(evn, XmlEvent::Attr { .. } | XmlEvent::StartTagEnd { .. }) {
+                declaration = false;
+                parent = stack.pop().unwrap();
+                if evn == (XmlEvent::PopElement { name: None }) {
+                    continue;
+                }
+            }
+
+            match evn {
+                XmlEvent::PushElement { name } => {
+                    let id = doc.push(parent, NodeKind::Element { name });
+                    stack.push(parent);
+                    parent = id;
+                    declaration = name.starts_with('?') || name.starts_with('!');
+                }
+                XmlEvent::PopElement { .. } => {
+                    // stray end tags are ignored
+                    if let Some(id) = stack.pop() {
+                        parent = id;
+                    }
+                }
+                XmlEvent::Attr { name, value } => {
+                    doc.attrs.push(Attribute { name, value });
+                    doc.nodes[parent as usize].attrs.end = doc.attrs.len() as u32;
+                }
+                XmlEvent::Text { text } => {
+                    doc.push(parent, NodeKind::Text { text });
+                }
+                XmlEvent::Comment { text } => {
+                    doc.push(parent, NodeKind::Comment { text });
+                }
+                XmlEvent::StartTagEnd { .. } => {}
+            }
+        }
+        doc
+    }
+
+    fn push(&mut self, parent: u32, kind: NodeKind<'a>) -> u32 {
+        let id = self.nodes.len() as u32;
+        assert!(id != NONE, "too many nodes");
+        let attrs = self.attrs.len() as u32;
+        let prev_sibling = self.nodes[parent as usize].last_child;
+        if prev_sibling == NONE {
+            self.nodes[parent as usize].first_child = id;
+        } else {
+            self.nodes[prev_sibling as usize].next_sibling = id;
+        }
+        self.nodes[parent as usize].last_child = id;
+        self.nodes.push(Node {
+            kind,
+            attrs: attrs..attrs,
+            parent,
+            prev_sibling,
+            next_sibling: NONE,
+            first_child: NONE,
+            last_child: NONE,
+        });
+        id
+    }
+
+    #[inline(always)]
+    pub fn source(&self) -> &'a str {
+        self.src
+    }
+
+    /// Number of nodes, including the document node
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// True if there are no nodes besides the document node
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.nodes.len() == 1
+    }
+
+    /// Node holding the top level nodes
+    #[inline(always)]
+    pub fn document(&self) -> NodeRef<'_, 'a> {
+        self.get(0)
+    }
+
+    /// First top level element that isn't a declaration
+    pub fn root(&self) -> Option<NodeRef<'_, 'a>> {
+        self.document().children().find(|node| {
+            matches!(node.kind(), NodeKind::Element { name } if !name.starts_with('?') && !name.starts_with('!'))
+        })
+    }
+
+    /// Node `id`, ids are in document order
+    ///
+    /// # Panics
+    ///
+    /// If `id` is out of bounds
+    #[inline(always)]
+    pub fn get(&self, id: NodeId) -> NodeRef<'_, 'a> {
+        assert!(id < self.nodes.len(), "node out of bounds");
+        NodeRef { doc: self, id }
+    }
+
+    /// All nodes in document order, starting with the document node
+    pub fn nodes(&self) -> impl Iterator<Item = NodeRef<'_, 'a>> + '_ {
+        (0..self.nodes.len()).map(|id| NodeRef { doc: self, id })
+    }
+
+    #[inline(always)]
+    fn node_ref(&self, id: u32) -> Option<NodeRef<'_, 'a>> {
+        (id != NONE).then_some(NodeRef {
+            doc: self,
+            id: id as usize,
+        })
+    }
+}
+
+/// [`Document`] that owns its source, so it can be stored in long lived structures
+///
+/// ```rust
+/// # use xml1::dom::OwnedDocument;
+/// let cache: Vec<OwnedDocument> = ["<a x=\"1\"/>", "<b/>"]
+///     .into_iter()
+///     .map(|src| OwnedDocument::parse(src.to_string()))
+///     .collect();
+/// assert_eq!(cache[0].document().root().unwrap().attr("x"), Some("1"));
+/// ```
+pub struct OwnedDocument {
+    // borrows `src`, only ever handed out with the lifetime of `self`
+    doc: ManuallyDrop<Document<'static>>,
+    src: NonNull<str>,
+}
+
+// SAFETY: it only holds shared references to `src`, that is never mutated
+unsafe impl Send for OwnedDocument {}
+unsafe impl Sync for OwnedDocument {}
+
+impl OwnedDocument {
+    pub fn parse(src: impl Into<Box<str>>) -> Self {
+        Self::parse_with(src, |src| XmlIter::from(src))
+    }
+
+    /// Builds the document from the iterator returned by `f`, use it to keep comments
+    pub fn parse_with(src: impl Into<Box<str>>, f: impl FnOnce(&str) -> XmlIter<'_>) -> Self {
+        // raw, so moving `self` around doesn't invalidate the borrows of `doc`
+        let src = NonNull::from(Box::leak(src.into()));
+        // SAFETY: `src` stays valid and unchanged until `doc` is dropped
+        let doc = Document::parse_iter(f(unsafe { src.as_ref() }));
+        Self {
+            doc: ManuallyDrop::new(doc),
+            src,
+        }
+    }
+
+    #[inline(always)]
+    pub fn source(&self) -> &str {
+        // SAFETY: `src` is valid for as long as `self`
+        unsafe { self.src.as_ref() }
+    }
+
+    #[inline(always)]
+    pub fn document(&self) -> &Document<'_> {
+        &self.doc
+    }
+
+    /// Drops the document and returns its source
+    pub fn into_source(self) -> Box<str> {
+        let mut this = ManuallyDrop::new(self);
+        // SAFETY: `doc` is dropped first and `this` is never used again
+        unsafe {
+            ManuallyDrop::drop(&mut this.doc);
+            Box::from_raw(this.src.as_ptr())
+        }
+    }
+}
+
+impl Drop for OwnedDocument {
+    fn drop(&mut self) {
+        // SAFETY: `doc` goes first as it borrows `src`, that was leaked from a `Box`
+        unsafe {
+            ManuallyDrop::drop(&mut self.doc);
+            drop(Box::from_raw(self.src.as_ptr()));
+        }
+    }
+}
+
+impl fmt::Debug for OwnedDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.document().fmt(f)
+    }
+}
+
+/// Handle to a node of a [`Document`]
+#[derive(Clone, Copy)]
+pub struct NodeRef<'d, 'a> {
+    doc: &'d Document<'a>,
+    id: NodeId,
+}
+
+impl PartialEq for NodeRef<'_, '_> {
+    fn eq(&self, other: &Self) -> bool {
+        core::ptr::eq(self.doc, other.doc) && self.id == other.id
+    }
+}
+
+impl Eq for NodeRef<'_, '_> {}
+
+impl fmt::Debug for NodeRef<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NodeRef")
+            .field("id", &self.id)
+            .field("kind", &self.kind())
+            .finish()
+    }
+}
+
+impl<'d, 'a> NodeRef<'d, 'a> {
+    #[inline(always)]
+    fn node(&self) -> &'d Node<'a> {
+        &self.doc.nodes[self.id]
+    }
+
+    #[inline(always)]
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+
+    #[inline(always)]
+    pub fn document(&self) -> &'d Document<'a> {
+        self.doc
+    }
+
+    #[inline(always)]
+    pub fn kind(&self) -> NodeKind<'a> {
+        self.node().kind
+    }
+
+    #[inline(always)]
+    pub fn is_element(&self) -> bool {
+        matches!(self.kind(), NodeKind::Element { .. })
+    }
+
+    #[inline(always)]
+    pub fn is_text(&self) -> bool {
+        matches!(self.kind(), NodeKind::Text { .. })
+    }
+
+    /// Name of the element
+    pub fn name(&self) -> Option<&'a str> {
+        match self.kind() {
+            NodeKind::Element { name } => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Text of a text or comment node, or the first text child of an element
+    pub fn text(&self) -> Option<&'a str> {
+        match self.kind() {
+            NodeKind::Text { text } | NodeKind::Comment { text } => Some(text),
+            NodeKind::Element { .. } => self.children().find_map(|child| match child.kind() {
+                NodeKind::Text { text } => Some(text),
+                _ => None,
+            }),
+            NodeKind::Document => None,
+        }
+    }
+
+    /// Attributes of an element in source order, empty for other nodes
+    pub fn attrs(&self) -> &'d [Attribute<'a>] {
+        let Range { start, end } = self.node().attrs;
+        &self.doc.attrs[start as usize..end as usize]
+    }
+
+    /// Value of the attribute `name`, valueless attributes return `Some("")`
+    pub fn attr(&self, name: &str) -> Option<&'a str> {
+        self.attrs()
+            .iter()
+            .find(|attr| attr.name == name)
+            .map(|attr| attr.value.unwrap_or(""))
+    }
+
+    pub fn has_attr(&self, name: &str) -> bool {
+        self.attrs().iter().any(|attr| attr.name == name)
+    }
+
+    pub fn parent(&self) -> Option<Self> {
+        self.doc.node_ref(self.node().parent)
+    }
+
+    pub fn prev_sibling(&self) -> Option<Self> {
+        self.doc.node_ref(self.node().prev_sibling)
+    }
+
+    pub fn next_sibling(&self) -> Option<Self> {
+        self.doc.node_ref(self.node().next_sibling)
+    }
+
+    pub fn first_child(&self) -> Option<Self> {
+        self.doc.node_ref(self.node().first_child)
+    }
+
+    pub fn last_child(&self) -> Option<Self> {
+        self.doc.node_ref(self.node().last_child)
+    }
+
+    pub fn has_children(&self) -> bool {
+        self.node().first_child != NONE
+    }
+
+    pub fn children(&self) -> Children<'d, 'a> {
+        Children {
+            next: self.first_child(),
+        }
+    }
+
+    /// Parent, grandparent and so on up to the document node
+    pub fn ancestors(&self) -> impl Iterator<Item = Self> {
+        core::iter::successors(self.parent(), |node| node.parent())
+    }
+
+    /// This node and every node under it in document order
+    pub fn descendants(&self) -> Descendants<'d, 'a> {
+        Descendants {
+            root: *self,
+            next: Some(*self),
+        }
+    }
+}
+
+/// Iterates over the children of a node, see [`NodeRef::children`]
+#[derive(Clone)]
+pub struct Children<'d, 'a> {
+    next: Option<NodeRef<'d, 'a>>,
+}
+
+impl<'d, 'a> Iterator for Children<'d, 'a> {
+    type Item = NodeRef<'d, 'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.next?;
+        self.next = node.next_sibling();
+        Some(node)
+    }
+}
+
+/// Iterates over a subtree in document order, see [`NodeRef::descendants`]
+#[derive(Clone)]
+pub struct Descendants<'d, 'a> {
+    root: NodeRef<'d, 'a>,
+    next: Option<NodeRef<'d, 'a>>,
+}
+
+impl<'d, 'a> Iterator for Descendants<'d, 'a> {
+    type Item = NodeRef<'d, 'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.next?;
+        self.next = node.first_child().or_else(|| {
+            // next sibling of the closest ancestor inside the subtree
+            let mut current = node;
+            loop {
+                if current == self.root {
+                    return None;
+                }
+                if let Some(next) = current.next_sibling() {
+                    return Some(next);
+                }
+                current = current.parent()?;
+            }
+        });
+        Some(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn navigation() {
+        let src = "<r>\n<a x=\"1\" y>a text<b/></a>\n<c/></r><s/>";
+        let doc = Document::parse(src);
+        let root = doc.root().unwrap();
+        assert_eq!(root.name(), Some("r"));
+        assert_eq!(root.parent(), Some(doc.document()));
+        assert_eq!(doc.document().children().count(), 2);
+
+        let children: Vec<_> = root.children().filter_map(|n| n.name()).collect();
+        assert_eq!(children, ["a", "c"]);
+        let a = root.first_child().unwrap();
+        assert_eq!(a.attrs().len(), 2);
+        assert_eq!(a.attr("x"), Some("1"));
+        assert_eq!(a.attr("y"), Some(""));
+        assert_eq!(a.attr("z"), None);
+        assert!(a.has_attr("y"));
+        assert_eq!(a.text(), Some("a text"));
+        assert_eq!(root.text(), None);
+
+        let b = a.last_child().unwrap();
+        assert_eq!(b.name(), Some("b"));
+        assert!(!b.has_children());
+        assert_eq!(b.attrs(), []);
+        assert_eq!(b.prev_sibling().unwrap().text(), Some("a text"));
+        assert_eq!(b.next_sibling(), None);
+        let ancestors: Vec<_> = b.ancestors().map(|n| n.id()).collect();
+        assert_eq!(ancestors, [a.id(), root.id(), 0]);
+
+        let names: Vec<_> = a.descendants().filter_map(|n| n.name()).collect();
+        assert_eq!(names, ["a", "b"]);
+    }
+
+    #[test]
+    fn declarations_and_comments() {
+        let src = "<?xml version=\"1.0\"?>\n<!-- c -->\n<r/>";
+        let doc = Document::parse_iter(XmlIter::from(src).with_comments(true));
+        let top: Vec<_> = doc.document().children().map(|n| n.kind()).collect();
+        assert_eq!(
+            top,
+            [
+                NodeKind::Element { name: "?xml" },
+                NodeKind::Comment { text: " c " },
+                NodeKind::Element { name: "r" },
+            ]
+        );
+        assert_eq!(doc.root().unwrap().name(), Some("r"));
+        assert_eq!(
+            doc.document().first_child().unwrap().attr("version"),
+            Some("1.0")
+        );
+    }
+
+    #[test]
+    fn owned() {
+        let src = std::string::String::from("<r><a x=\"1\">text</a></r>");
+        let owned = Box::new(OwnedDocument::parse_with(src, |src| {
+            XmlIter::from(src).with_comments(true)
+        }));
+        // moving it around keeps the borrows valid
+        let owned = std::vec![*owned].pop().unwrap();
+        let a = owned.document().root().unwrap().first_child().unwrap();
+        assert_eq!(a.attr("x"), Some("1"));
+        assert_eq!(a.text(), Some("text"));
+        assert!(owned.source().starts_with("<r>"));
+        assert_eq!(&*owned.into_source(), "<r><a x=\"1\">text</a></r>");
+    }
+
+    #[test]
+    fn all_elements() {
+        for src in [
+            include_str!("../benches/files/fonts.conf"),
+            include_str!("../benches/files/huge.xml"),
+            include_str!("../benches/files/large.plist"),
+            include_str!("../benches/files/medium.svg"),
+        ] {
+            let doc = Document::parse(src);
+            let names: Vec<_> = doc
+                .document()
+                .descendants()
+                .filter_map(|n| n.name())
+                .collect();
+            let expected: Vec<_> = XmlIter::from(src)
+                .filter_map(|evn| match evn {
+                    XmlEvent::PushElement { name } => Some(name),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(names, expected);
+            let attrs: usize = doc.nodes().map(|n| n.attrs().len()).sum();
+            let expected = XmlIter::from(src)
+                .filter(|evn| matches!(evn, XmlEvent::Attr { .. }))
+                .count();
+            assert_eq!(attrs, expected);
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use crate::{scan, XmlEvent, XmlIter};
+
+/// Start tag of an element returned by [`XmlIter::next_element`], attributes are parsed
+/// on demand from the raw start tag, so they can be iterated any number of times
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ElementStart<'a> {
+    name: &'a str,
+    start_tag: &'a str,
+    self_closing: bool,
+}
+
+impl<'a> ElementStart<'a> {
+    #[inline(always)]
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// Raw source of the start tag, from its `'<'` up to its `'>'`
+    #[inline(always)]
+    pub fn start_tag(&self) -> &'a str {
+        self.start_tag
+    }
+
+    #[inline(always)]
+    pub fn is_self_closing(&self) -> bool {
+        self.self_closing
+    }
+
+    /// Names and values of the attributes, in the same order as the [`XmlEvent::Attr`]s
+    pub fn attrs(&self) -> Attrs<'a> {
+        let mut iter = XmlIter::from(self.start_tag).with_start_tag_end(true);
+        iter.input.seek(1 + self.name.len());
+        iter.prop = true;
+        Attrs { iter }
+    }
+
+    /// Value of the first attribute called `name`, attributes without value give `""`
+    pub fn get(&self, name: &str) -> Option<&'a str> {
+        self.attrs()
+            .find(|(attr, _)| *attr == name)
+            .map(|(_, value)| value.unwrap_or(""))
+    }
+
+    pub fn has_attr(&self, name: &str) -> bool {
+        self.attrs().any(|(attr, _)| attr == name)
+    }
+}
+
+/// Iterates over the attributes of an [`ElementStart`]
+#[derive(Clone)]
+pub struct Attrs<'a> {
+    iter: XmlIter<'a>,
+}
+
+impl<'a> Iterator for Attrs<'a> {
+    type Item = (&'a str, Option<&'a str>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.iter.next()? {
+            XmlEvent::Attr { name, value } => Some((name, value)),
+            _ => {
+                // past the end of the start tag
+                self.iter.prop = false;
+                self.iter.pending_pop = false;
+                self.iter.input.seek(self.iter.input.source().len());
+                None
+            }
+        }
+    }
+}
+
+impl<'a> XmlIter<'a> {
+    /// Skips to the next start tag and consumes it whole, the iterator resumes at the
+    /// content of the element
+    ///
+    /// Any event before the start tag is skipped, like texts and end tags, including the
+    /// attributes left of the current start tag. The [`XmlEvent::PopElement`] of the element
+    /// is still returned by [`XmlIter::next`], so flat events stay balanced.
+    ///
+    /// ```rust
+    /// # use xml1::{XmlEvent, XmlIter};
+    /// let mut iter = XmlIter::from("<r><a x=\"1\" clip>text</a><b/></r>");
+    /// assert_eq!(iter.next_element().unwrap().name(), "r");
+    /// let a = iter.next_element().unwrap();
+    /// assert_eq!(a.get("x"), Some("1"));
+    /// assert_eq!(a.get("clip"), Some(""));
+    /// assert_eq!(a.attrs().count(), 2);
+    /// assert_eq!(iter.next(), Some(XmlEvent::Text { text: "text" }));
+    /// assert_eq!(iter.next(), Some(XmlEvent::PopElement { name: Some("a") }));
+    /// assert!(iter.next_element().unwrap().is_self_closing());
+    /// assert_eq!(iter.next(), Some(XmlEvent::PopElement { name: None }));
+    /// ```
+    pub fn next_element(&mut self) -> Option<ElementStart<'a>> {
+        self.pending_pop = false;
+        if self.prop {
+            self.skip_start_tag();
+        }
+
+        let name = loop {
+            match self.document_events()? {
+                XmlEvent::PushElement { name } => break name,
+                _ => continue,
+            }
+        };
+        let (end, self_closing) = self.skip_start_tag();
+        self.pending_pop = self_closing;
+        Some(ElementStart {
+            name,
+            start_tag: &self.input.source()[self.start..end],
+            self_closing,
+        })
+    }
+
+    /// Moves past the `'>'` of the current start tag
+    fn skip_start_tag(&mut self) -> (usize, bool) {
+        let src = self.input.source();
+        let Some((end, self_closing)) =
+            scan::tag_end(src.as_bytes(), self.input.offset_from_source_str())
+        else {
+            panic!("unexpected end of file at {}", self.current_position());
+        };
+        self.input.seek(end);
+        self.prop = false;
+        (end, self_closing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::Ordering;
+
+    use super::*;
+    use crate::tests::ALLOCATIONS_COUNT;
+
+    #[test]
+    fn same_as_events() {
+        // reset allocations
+        ALLOCATIONS_COUNT.store(0, Ordering::Relaxed);
+
+        let src = include_str!("../benches/files/medium.svg");
+        let mut events = XmlIter::from(src).with_start_tag_end(true);
+        let mut elements = XmlIter::from(src);
+        while let Some(evn) = events.next() {
+            let XmlEvent::PushElement { name } = evn else {
+                continue;
+            };
+            let element = elements.next_element().unwrap();
+            assert_eq!(element.name(), name);
+            assert!(element.start_tag().starts_with('<'));
+            assert!(element.start_tag().ends_with('>'));
+            let mut attrs = element.attrs();
+            loop {
+                match events.next() {
+                    Some(XmlEvent::Attr { name, value }) => {
+                        assert_eq!(attrs.next(), Some((name, value)))
+                    }
+                    Some(XmlEvent::StartTagEnd { self_closing }) => {
+                        assert_eq!(element.is_self_closing(), self_closing);
+                        break;
+                    }
+                    evn => panic!("unexpected {:?}", evn),
+                }
+            }
+            assert_eq!(attrs.next(), None);
+        }
+        assert!(elements.next_element().is_none());
+
+        assert_eq!(ALLOCATIONS_COUNT.load(Ordering::Relaxed), 0, "allocated");
+    }
+
+    #[test]
+    fn mixed_with_events() {
+        let src = r#"<?xml version="1.0"?><r a="\"/>" <!-- c --> b><x/>text</r>"#;
+        let mut iter = XmlIter::from(src);
+        let decl = iter.next_element().unwrap();
+        assert_eq!(decl.name(), "?xml");
+        assert_eq!(decl.get("version"), Some("1.0"));
+
+        // attributes left of the current start tag are skipped
+        assert_eq!(iter.next(), Some(XmlEvent::PushElement { name: "r" }));
+        assert_eq!(
+            iter.next(),
+            Some(XmlEvent::Attr {
+                name: "a",
+                value: Some(r#"\"/>"#)
+            })
+        );
+        let x = iter.next_element().unwrap();
+        assert_eq!((x.name(), x.start_tag()), ("x", "<x/>"));
+        assert_eq!(x.attrs().next(), None);
+        assert_eq!(iter.next(), Some(XmlEvent::PopElement { name: None }));
+        assert_eq!(iter.next(), Some(XmlEvent::Text { text: "text" }));
+
+        let mut iter = XmlIter::from(src);
+        iter.next_element();
+        let r = iter.next_element().unwrap();
+        assert_eq!(
+            r.attrs().collect::<std::vec::Vec<_>>(),
+            [("a", Some(r#"\"/>"#)), ("b", None)]
+        );
+        assert!(r.has_attr("b"));
+        assert!(!r.has_attr("c"));
+        assert_eq!(r.get("missing"), None);
+        assert_eq!(iter.next_element().unwrap().name(), "x");
+        assert!(iter.next_element().is_none());
+    }
+}
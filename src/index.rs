@@ -0,0 +1,304 @@
+//! Structural index of a document, for random access into large static files.
+//!
+//! Every element is recorded in document order with its byte offsets, depth and links
+//! to its parent and next sibling, from there any element can be parsed on demand.
+//!
+//! ```rust
+//! # use xml1::{index::Index, XmlEvent};
+//! let src = "<r><a/><b x=\"1\"><c/></b></r>";
+//! let index = Index::build(src);
+//! let b = index.children(0).nth(1).unwrap();
+//! assert_eq!(index.name(src, b), "b");
+//! let mut iter = index.iter_at(src, b);
+//! assert_eq!(iter.next(), Some(XmlEvent::PushElement { name: "b" }));
+//! ```
+
+use alloc::vec::Vec;
+
+use crate::{XmlEvent, XmlIter};
+
+/// Used in place of a node index for missing links
+pub const NONE: u32 = u32::MAX;
+
+/// Compact entry of the [`Index`], offsets are in bytes relative to the indexed source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Node {
+    /// Offset of the `'<'` of the start tag
+    pub start: u32,
+    /// Offset past the `'>'` of the end tag, or the end of the source for unclosed elements
+    pub end: u32,
+    /// Length of the element name, that starts right after `start`
+    pub name_len: u32,
+    /// Zero for top level elements
+    pub depth: u32,
+    pub parent: u32,
+    pub next_sibling: u32,
+}
+
+const NODE_WORDS: usize = 6;
+
+/// Every element of a document in a single array, see the [module docs](self)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Index {
+    nodes: Vec<Node>,
+}
+
+impl Index {
+    /// Indexes every element of `src`, which must be smaller than 4 GiB
+    pub fn build(src: &str) -> Self {
+        assert!(
+            src.len() < NONE as usize,
+            "source is too large to be indexed"
+        );
+
+        let mut nodes: Vec<Node> = Vec::new();
+        // (node, last child) of the open elements
+        let mut stack: Vec<(u32, u32)> = Vec::new();
+        let mut iter = XmlIter::from(src);
+        while let Some(evn) = iter.next() {
+            match evn {
+                XmlEvent::PushElement { name } => {
+                    let id = nodes.len() as u32;
+                    let parent = match stack.last_mut() {
+                        Some((parent, last_child)) => {
+                            if *last_child != NONE {
+                                nodes[*last_child as usize].next_sibling = id;
+                            }
+                            *last_child = id;
+                            *parent
+                        }
+                        None => {
+                            // link top level elements as siblings too
+                            if let Some(prev) = nodes.iter().rposition(|n| n.parent == NONE) {
+                                nodes[prev].next_sibling = id;
+                            }
+                            NONE
+                        }
+                    };
+                    nodes.push(Node {
+                        start: iter.start as u32,
+                        end: src.len() as u32,
+                        name_len: name.len() as u32,
+                        depth: stack.len() as u32,
+                        parent,
+                        next_sibling: NONE,
+                    });
+                    stack.push((id, NONE));
+                }
+                XmlEvent::PopElement { .. } => {
+                    // stray end tags are ignored
+                    if let Some((id, _)) = stack.pop() {
+                        nodes[id as usize].end = iter.input.offset_from_source_str() as u32;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Self { nodes }
+    }
+
+    #[inline(always)]
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    #[inline(always)]
+    pub fn get(&self, id: usize) -> Option<&Node> {
+        self.nodes.get(id)
+    }
+
+    /// Name of the element `id`, `src` must be the indexed source
+    pub fn name<'a>(&self, src: &'a str, id: usize) -> &'a str {
+        let node = &self.nodes[id];
+        let start = node.start as usize + 1;
+        &src[start..start + node.name_len as usize]
+    }
+
+    /// Raw source of the element `id`, `src` must be the indexed source
+    pub fn outer_xml<'a>(&self, src: &'a str, id: usize) -> &'a str {
+        let node = &self.nodes[id];
+        &src[node.start as usize..node.end as usize]
+    }
+
+    /// Parses only the subtree of the element `id`, `src` must be the indexed source
+    pub fn iter_at<'a>(&self, src: &'a str, id: usize) -> XmlIter<'a> {
+        XmlIter::from(self.outer_xml(src, id))
+    }
+
+    /// Ids of the top level elements
+    pub fn roots(&self) -> Siblings<'_> {
+        Siblings {
+            nodes: &self.nodes,
+            next: if self.nodes.is_empty() { NONE } else { 0 },
+        }
+    }
+
+    /// Ids of the children of the element `id`
+    pub fn children(&self, id: usize) -> Siblings<'_> {
+        // nodes are in document order so the first child always comes next
+        let next = match self.nodes.get(id + 1) {
+            Some(node) if node.parent == id as u32 => id as u32 + 1,
+            _ => NONE,
+        };
+        Siblings {
+            nodes: &self.nodes,
+            next,
+        }
+    }
+
+    /// Serializes the index as little endian `u32`s, so it can be stored on disk
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(4 + self.nodes.len() * NODE_WORDS * 4);
+        bytes.extend_from_slice(&(self.nodes.len() as u32).to_le_bytes());
+        for node in &self.nodes {
+            for word in [
+                node.start,
+                node.end,
+                node.name_len,
+                node.depth,
+                node.parent,
+                node.next_sibling,
+            ] {
+                bytes.extend_from_slice(&word.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    /// Reads an index written by [`Index::to_bytes`], `None` if `bytes` have the wrong size
+    /// or the nodes aren't consistent, like links to missing nodes
+    ///
+    /// Offsets can't be checked without the source, so it must be the same one that was indexed
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let mut words = bytes
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()));
+        let len = words.next()? as usize;
+        if Some(bytes.len()) != len.checked_mul(NODE_WORDS * 4)?.checked_add(4) {
+            return None;
+        }
+
+        let mut nodes = Vec::with_capacity(len);
+        for id in 0..len as u32 {
+            let mut next = || words.next().unwrap();
+            let node = Node {
+                start: next(),
+                end: next(),
+                name_len: next(),
+                depth: next(),
+                parent: next(),
+                next_sibling: next(),
+            };
+            // nodes are in document order, parents come before and siblings after
+            let valid = node.name_len < node.end.checked_sub(node.start)?
+                && (node.parent == NONE || node.parent < id)
+                && (node.next_sibling == NONE
+                    || (id < node.next_sibling && node.next_sibling < len as u32));
+            if !valid {
+                return None;
+            }
+            nodes.push(node);
+        }
+        Some(Self { nodes })
+    }
+}
+
+/// Iterates over the ids of sibling elements, see [`Index::children`]
+#[derive(Clone)]
+pub struct Siblings<'i> {
+    nodes: &'i [Node],
+    next: u32,
+}
+
+impl Iterator for Siblings<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.next == NONE {
+            return None;
+        }
+        let id = self.next as usize;
+        self.next = self.nodes[id].next_sibling;
+        Some(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links() {
+        let src = "<r><a><b/></a><c>text</c><d/></r><s/>";
+        let index = Index::build(src);
+        let names: Vec<_> = (0..index.len()).map(|id| index.name(src, id)).collect();
+        assert_eq!(names, ["r", "a", "b", "c", "d", "s"]);
+        assert_eq!(index.roots().collect::<Vec<_>>(), [0, 5]);
+        assert_eq!(index.children(0).collect::<Vec<_>>(), [1, 3, 4]);
+        assert_eq!(index.children(1).collect::<Vec<_>>(), [2]);
+        assert_eq!(index.children(2).count(), 0);
+        assert_eq!(index.nodes()[2].depth, 2);
+        assert_eq!(index.nodes()[2].parent, 1);
+        assert_eq!(index.outer_xml(src, 3), "<c>text</c>");
+        assert_eq!(index.outer_xml(src, 5), "<s/>");
+    }
+
+    #[test]
+    fn subtree_events() {
+        let src = include_str!("../benches/files/huge.xml");
+        let index = Index::build(src);
+        let events: Vec<_> = XmlIter::from(src).collect();
+        let mut pushes = events
+            .iter()
+            .enumerate()
+            .filter(|(_, evn)| matches!(evn, XmlEvent::PushElement { .. }));
+
+        for id in (0..index.len()).step_by(97) {
+            let (start, _) = pushes.nth(if id == 0 { 0 } else { 96 }).unwrap();
+            let node = index.nodes()[id];
+            if node.end as usize == src.len() {
+                // unclosed, like <?xml ...?>
+                continue;
+            }
+            let subtree: Vec<_> = index.iter_at(src, id).collect();
+            assert_eq!(subtree[..], events[start..start + subtree.len()]);
+            assert!(matches!(subtree.last(), Some(XmlEvent::PopElement { .. })));
+        }
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let src = include_str!("../benches/files/medium.svg");
+        let index = Index::build(src);
+        let bytes = index.to_bytes();
+        assert_eq!(Index::from_bytes(&bytes), Some(index.clone()));
+        assert_eq!(Index::from_bytes(&bytes[1..]), None);
+        assert_eq!(Index::from_bytes(&[]), None);
+
+        let corrupt = |word: usize, value: u32| {
+            let mut bytes = bytes.clone();
+            let word = 4 + word * 4;
+            bytes[word..word + 4].copy_from_slice(&value.to_le_bytes());
+            Index::from_bytes(&bytes)
+        };
+        let last = index.len() - 1;
+        // next sibling of the root element, past the last node
+        assert_eq!(corrupt(5, index.len() as u32), None);
+        // parent of the last node, itself
+        assert_eq!(corrupt(last * NODE_WORDS + 4, last as u32), None);
+        // end of the first node, before its start
+        assert_eq!(corrupt(1, 0), None);
+        assert_eq!(corrupt(5, NONE).map(|index| index.roots().count()), Some(1));
+    }
+}
@@ -1,6 +1,7 @@
 //! Basic usage is:
 //!
 //! ```rust
+//! # use xml1::XmlIter;
 //! for evn in XmlIter::from("<a min=\"0, 0\">Some Text</a>") {
 //!     dbg!(evn);
 //!     // do something with evn
@@ -10,11 +11,83 @@
 //! You may want to keep a stack around to push values and modify it's attributes.
 //!
 //! Less than sign `'<'` must be escaped during texts sequeces
+//!
+//! Use [`XmlWriter`] to write events back as markup, or [`minify()`] to strip comments and whitespace.
+//!
+//! Errors panic with their line and column, use [`XmlIter::from_sub_str`] for documents embedded
+//! in other files, so they are relative to the whole file.
+//!
+//! Use [`XmlIter::next_element`] to pull whole start tags with their attributes at once.
+//!
+//! Use [`PathFilter`] to only get the events under a path like `plist/dict/array`,
+//! or implement an [`XmlVisitor`] to get callbacks instead of events.
+//!
+//! Enable the `memchr` feature to use the [`memchr`](https://docs.rs/memchr) crate for the vectorized scanning.
+//!
+//! Enable the `mmap` feature to parse files straight from disk using [`MappedDocument`].
+//!
+//! Enable the `alloc` feature to build a `dom` tree and query it with `xpath` or `css` selectors, an `index` of the elements for random access,
+//! a compact `tape` of events for repeated traversals, to `pretty` print documents or
+//! to edit them without losing any formatting with the `cst` or a lighter `patch`.
+//! The `cli` feature builds the `xml1` binary with the formatter.
+//!
+//! Enable the `serde` feature to deserialize documents into Rust types and back,
+//! see the `de` and `ser` modules.
+//!
+//! Enable the `rayon` feature to parse large documents on multiple threads, see the `par` module.
 
 #![cfg_attr(not(test), no_std)]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
 mod chars;
+mod element;
+mod minify;
+mod path;
+mod position;
+mod scan;
+mod visit;
+mod writer;
 use chars::Chars;
+pub use element::{Attrs, ElementStart};
+pub use minify::{minify, MinifyStats};
+pub use path::PathFilter;
+pub use position::Position;
+pub use visit::{visit, visit_iter, ControlFlow, XmlVisitor};
+#[cfg(feature = "std")]
+pub use writer::IoWriter;
+pub use writer::XmlWriter;
+
+#[cfg(feature = "mmap")]
+mod mmap;
+#[cfg(feature = "mmap")]
+pub use mmap::MappedDocument;
+
+#[cfg(feature = "alloc")]
+pub mod css;
+#[cfg(feature = "alloc")]
+pub mod cst;
+#[cfg(feature = "serde")]
+pub mod de;
+#[cfg(feature = "alloc")]
+pub mod dom;
+#[cfg(feature = "alloc")]
+pub mod index;
+#[cfg(feature = "rayon")]
+pub mod par;
+#[cfg(feature = "alloc")]
+pub mod patch;
+#[cfg(feature = "alloc")]
+pub mod pretty;
+#[cfg(feature = "serde")]
+pub mod ser;
+#[cfg(feature = "alloc")]
+pub mod tape;
+#[cfg(feature = "alloc")]
+pub mod xpath;
 
 /// Xml events returned from the [`XmlIter`]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -32,12 +105,125 @@ pub enum XmlEvent<'a> {
     Text {
         text: &'a str,
     },
+    /// Only returned when enabled with [`XmlIter::with_comments`]
+    Comment {
+        text: &'a str,
+    },
+    /// End of the start tag, after the last attribute, only returned when enabled
+    /// with [`XmlIter::with_start_tag_end`]. A self closing element is followed by
+    /// its `PopElement { name: None }`
+    StartTagEnd {
+        self_closing: bool,
+    },
+}
+
+#[cfg(feature = "alloc")]
+impl XmlEvent<'_> {
+    /// Copies the event so it can outlive its source
+    pub fn to_owned(&self) -> OwnedXmlEvent {
+        use alloc::string::ToString;
+        match *self {
+            XmlEvent::PushElement { name } => OwnedXmlEvent::PushElement {
+                name: name.to_string(),
+            },
+            XmlEvent::PopElement { name } => OwnedXmlEvent::PopElement {
+                name: name.map(ToString::to_string),
+            },
+            XmlEvent::Attr { name, value } => OwnedXmlEvent::Attr {
+                name: name.to_string(),
+                value: value.map(ToString::to_string),
+            },
+            XmlEvent::Text { text } => OwnedXmlEvent::Text {
+                text: text.to_string(),
+            },
+            XmlEvent::Comment { text } => OwnedXmlEvent::Comment {
+                text: text.to_string(),
+            },
+            XmlEvent::StartTagEnd { self_closing } => OwnedXmlEvent::StartTagEnd { self_closing },
+        }
+    }
+}
+
+/// [`XmlEvent`] that owns its strings, see [`XmlEvent::to_owned`]
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OwnedXmlEvent {
+    PushElement {
+        name: alloc::string::String,
+    },
+    PopElement {
+        name: Option<alloc::string::String>,
+    },
+    Attr {
+        name: alloc::string::String,
+        value: Option<alloc::string::String>,
+    },
+    Text {
+        text: alloc::string::String,
+    },
+    Comment {
+        text: alloc::string::String,
+    },
+    StartTagEnd {
+        self_closing: bool,
+    },
+}
+
+#[cfg(feature = "alloc")]
+impl OwnedXmlEvent {
+    /// Borrows the event back, so it can be given to an [`XmlWriter`]
+    pub fn as_event(&self) -> XmlEvent<'_> {
+        match self {
+            OwnedXmlEvent::PushElement { name } => XmlEvent::PushElement { name },
+            OwnedXmlEvent::PopElement { name } => XmlEvent::PopElement {
+                name: name.as_deref(),
+            },
+            OwnedXmlEvent::Attr { name, value } => XmlEvent::Attr {
+                name,
+                value: value.as_deref(),
+            },
+            OwnedXmlEvent::Text { text } => XmlEvent::Text { text },
+            OwnedXmlEvent::Comment { text } => XmlEvent::Comment { text },
+            OwnedXmlEvent::StartTagEnd { self_closing } => XmlEvent::StartTagEnd {
+                self_closing: *self_closing,
+            },
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl From<XmlEvent<'_>> for OwnedXmlEvent {
+    fn from(evn: XmlEvent<'_>) -> Self {
+        evn.to_owned()
+    }
 }
 
 /// Xml parser, it iterates over a stream of `chars` returning [`XmlEvent`]s
+///
+/// Cloning is cheap, the clone continues from the same position
+#[derive(Clone)]
 pub struct XmlIter<'a> {
     input: Chars<'a>,
     prop: bool,
+    /// Offset of the `'<'` of the last pushed element
+    start: usize,
+    comments: bool,
+    start_tag_end: bool,
+    /// `PopElement { name: None }` to return after a `StartTagEnd { self_closing: true }`
+    pending_pop: bool,
+    /// Position of the start of the source
+    base: Position,
+    /// Made by [`XmlIter::from_attrs`], the input may end right after an attribute name
+    fragment: bool,
+}
+
+/// Saved position of an [`XmlIter`], see [`XmlIter::checkpoint`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    pos: usize,
+    prop: bool,
+    start: usize,
+    pending_pop: bool,
 }
 
 impl<'a> From<&'a str> for XmlIter<'a> {
@@ -45,6 +231,12 @@ impl<'a> From<&'a str> for XmlIter<'a> {
         Self {
             input: input.into(),
             prop: false,
+            start: 0,
+            comments: false,
+            start_tag_end: false,
+            pending_pop: false,
+            base: Position::START,
+            fragment: false,
         }
     }
 }
@@ -53,6 +245,10 @@ impl<'a> Iterator for XmlIter<'a> {
     type Item = XmlEvent<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if self.pending_pop {
+            self.pending_pop = false;
+            return Some(XmlEvent::PopElement { name: None });
+        }
         if self.prop {
             self.element_events()
         } else {
@@ -62,58 +258,207 @@ impl<'a> Iterator for XmlIter<'a> {
 }
 
 impl<'a> XmlIter<'a> {
+    /// Also return [`XmlEvent::Comment`]s, they are ignored by default
+    ///
+    /// ```rust
+    /// # use xml1::{XmlEvent, XmlIter};
+    /// let mut iter = XmlIter::from("<!-- note --><a/>").with_comments(true);
+    /// assert_eq!(iter.next(), Some(XmlEvent::Comment { text: " note " }));
+    /// ```
+    pub fn with_comments(mut self, enabled: bool) -> Self {
+        self.comments = enabled;
+        self
+    }
+
+    /// Also return [`XmlEvent::StartTagEnd`]s, so the end of the attributes is known
+    /// without looking at the next event
+    ///
+    /// ```rust
+    /// # use xml1::{XmlEvent, XmlIter};
+    /// let mut iter = XmlIter::from("<a x=\"1\"/>").with_start_tag_end(true);
+    /// assert_eq!(iter.nth(2), Some(XmlEvent::StartTagEnd { self_closing: true }));
+    /// assert_eq!(iter.next(), Some(XmlEvent::PopElement { name: None }));
+    /// ```
+    pub fn with_start_tag_end(mut self, enabled: bool) -> Self {
+        self.start_tag_end = enabled;
+        self
+    }
+
+    /// Saves the current position, to go back to it later with [`XmlIter::restore`]
+    ///
+    /// ```rust
+    /// # use xml1::{XmlEvent, XmlIter};
+    /// let mut iter = XmlIter::from("<a x=\"1\"/>");
+    /// iter.next();
+    /// let checkpoint = iter.checkpoint();
+    /// assert_eq!(iter.next(), Some(XmlEvent::Attr { name: "x", value: Some("1") }));
+    /// iter.restore(checkpoint);
+    /// assert_eq!(iter.next(), Some(XmlEvent::Attr { name: "x", value: Some("1") }));
+    /// ```
+    #[inline(always)]
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            pos: self.input.offset_from_source_str(),
+            prop: self.prop,
+            start: self.start,
+            pending_pop: self.pending_pop,
+        }
+    }
+
+    /// Goes back (or forward) to a position saved by [`XmlIter::checkpoint`],
+    /// the `checkpoint` must come from an iterator over the same source
+    #[inline(always)]
+    pub fn restore(&mut self, checkpoint: Checkpoint) {
+        self.input.seek(checkpoint.pos);
+        self.prop = checkpoint.prop;
+        self.start = checkpoint.start;
+        self.pending_pop = checkpoint.pending_pop;
+    }
+
+    /// Returns the next event without consuming it
+    ///
+    /// ```rust
+    /// # use xml1::{XmlEvent, XmlIter};
+    /// let mut iter = XmlIter::from("<a/>");
+    /// assert_eq!(iter.peek(), Some(XmlEvent::PushElement { name: "a" }));
+    /// assert_eq!(iter.next(), Some(XmlEvent::PushElement { name: "a" }));
+    /// ```
+    #[inline(always)]
+    pub fn peek(&self) -> Option<XmlEvent<'a>> {
+        self.clone().next()
+    }
+
+    /// Skips the element just pushed and all of its children, returning the raw source
+    /// of the whole subtree, from its `'<'` up to the `'>'` of the end tag.
+    ///
+    /// Must be called right after a [`XmlEvent::PushElement`], before any event other than
+    /// [`XmlEvent::Attr`], the matching [`XmlEvent::PopElement`] is skipped as well.
+    /// Only `'<'` are looked at while tracking the depth, so this is much faster than
+    /// pulling and discarding every event.
+    ///
+    /// ```rust
+    /// # use xml1::{XmlEvent, XmlIter};
+    /// let mut iter = XmlIter::from("<a><metadata><b/></metadata><c/></a>");
+    /// assert_eq!(iter.next(), Some(XmlEvent::PushElement { name: "a" }));
+    /// assert_eq!(iter.next(), Some(XmlEvent::PushElement { name: "metadata" }));
+    /// assert_eq!(iter.skip_element(), "<metadata><b/></metadata>");
+    /// assert_eq!(iter.next(), Some(XmlEvent::PushElement { name: "c" }));
+    /// ```
+    pub fn skip_element(&mut self) -> &'a str {
+        let end = self.bounds().end;
+        self.input.seek(end);
+        self.prop = false;
+        &self.input.source()[self.start..end]
+    }
+
+    /// Raw source of the content of the element just pushed, between its start and end tags,
+    /// empty for self closing elements. It doesn't consume any input.
+    ///
+    /// Same requirements as [`XmlIter::skip_element`]
+    ///
+    /// ```rust
+    /// # use xml1::{XmlEvent, XmlIter};
+    /// let mut iter = XmlIter::from("<p>Some <b>bold</b> text</p>");
+    /// assert_eq!(iter.next(), Some(XmlEvent::PushElement { name: "p" }));
+    /// assert_eq!(iter.inner_xml(), "Some <b>bold</b> text");
+    /// assert_eq!(iter.outer_xml(), "<p>Some <b>bold</b> text</p>");
+    /// ```
+    pub fn inner_xml(&self) -> &'a str {
+        let bounds = self.bounds();
+        &self.input.source()[bounds.content..bounds.content_end]
+    }
+
+    /// Raw source of the whole element just pushed, including its start and end tags.
+    /// It doesn't consume any input.
+    ///
+    /// Same requirements as [`XmlIter::skip_element`]
+    pub fn outer_xml(&self) -> &'a str {
+        &self.input.source()[self.start..self.bounds().end]
+    }
+
+    fn bounds(&self) -> scan::Bounds {
+        assert!(self.prop, "must be called right after a `PushElement`");
+        let src = self.input.source();
+        match scan::element_bounds(src.as_bytes(), self.input.offset_from_source_str()) {
+            Ok(bounds) | Err(bounds) => bounds,
+        }
+    }
+
     #[inline(always)]
     fn ignore_whitespace(&mut self) {
-        while let Some(ch) = self.input.head() {
-            // ignore right-to-left mark to better support these langs
-            if ch.is_whitespace() || ch == '\u{200F}' {
-                self.input.next();
+        while let Some(byte) = self.input.head_byte() {
+            if byte < 0x80 {
+                if is_ascii_whitespace(byte) {
+                    self.input.advance(1);
+                } else {
+                    break;
+                }
             } else {
-                break;
+                let ch = self.input.head().unwrap();
+                // ignore right-to-left mark to better support these langs
+                if ch.is_whitespace() || ch == '\u{200F}' {
+                    self.input.advance(ch.len_utf8());
+                } else {
+                    break;
+                }
             }
         }
     }
 
-    fn ignore_comment(&mut self) {
+    /// Consumes chars until a whitespace or a byte matching `delimiter`, returns the byte
+    /// where it stopped or `None` at the end of the input
+    #[inline(always)]
+    fn skip_name(&mut self, delimiter: impl Fn(u8) -> bool) -> Option<u8> {
+        while let Some(byte) = self.input.head_byte() {
+            if byte < 0x80 {
+                if is_ascii_whitespace(byte) || delimiter(byte) {
+                    return Some(byte);
+                }
+                self.input.advance(1);
+            } else {
+                let ch = self.input.head().unwrap();
+                if ch.is_whitespace() {
+                    return Some(byte);
+                }
+                self.input.advance(ch.len_utf8());
+            }
+        }
+        None
+    }
+
+    /// Consumes a comment returning its text
+    fn comment(&mut self) -> &'a str {
         // expects input to be head = Some('!'), tail = "--"
         debug_assert!(self.input.head() == Some('!'));
         debug_assert!(self.input.tail().starts_with("--"));
         self.input.next(); // head = Some('-'), tail = "-..."
         self.input.next(); // head = Some('-'), tail = "..."
-
-        loop {
-            let rem = self.input.tail();
-            if rem.starts_with("-->") {
-                // head = Some(?), tail = "-->..."
-                self.input.next(); // head = Some('-'), tail = "->..."
-                self.input.next(); // head = Some('-'), tail = ">..."
-                self.input.next(); // head = Some('>'), tail = "..."
-                self.input.next(); // head = ?, tail = "..."
-                break;
-            } else {
-                if self.input.next() == None {
-                    break;
-                }
-            }
+        self.input.next(); // head = ?, the closing "-->" can't overlap with the opening "<!--"
+        let start = self.input.offset_from_source_str();
+        let mut end = self.input.source().len();
+        if self.input.skip_past("-->") {
+            end = self.input.offset_from_source_str() - 3;
         }
+        &self.input.source()[start..end]
     }
 
     fn document_events(&mut self) -> Option<XmlEvent<'a>> {
         loop {
             self.ignore_whitespace();
 
-            match self.input.head() {
-                Some('<') => {
-                    // note: the tail doesn't contains the head
-                    let rem = self.input.tail();
+            match self.input.head_byte() {
+                Some(b'<') => {
                     // consume '<'
-                    self.input.next();
-                    if rem.starts_with("/") {
+                    self.input.advance(1);
+                    if self.input.head_byte() == Some(b'/') {
                         // consume '/'
-                        self.input.next();
+                        self.input.advance(1);
                         return self.pop_element();
-                    } else if rem.starts_with("!--") {
-                        self.ignore_comment();
+                    } else if self.input.rest().starts_with("!--") {
+                        let text = self.comment();
+                        if self.comments {
+                            return Some(XmlEvent::Comment { text });
+                        }
                         continue;
                     } else {
                         return self.push_element();
@@ -132,13 +477,7 @@ impl<'a> XmlIter<'a> {
 
     fn push_text(&mut self) -> Option<XmlEvent<'a>> {
         let cursor = self.input.cursor();
-        while let Some(ch) = self.input.head() {
-            if ch == '\n' || ch == '<' {
-                break;
-            } else {
-                self.input.next();
-            }
-        }
+        self.input.skip_until2(b'\n', b'<');
 
         Some(XmlEvent::Text {
             text: self.input.sub_str_from_cursor(cursor).trim_end(),
@@ -147,85 +486,106 @@ impl<'a> XmlIter<'a> {
 
     fn push_element(&mut self) -> Option<XmlEvent<'a>> {
         let cursor = self.input.cursor();
-        while let Some(ch) = self.input.head() {
-            if !ch.is_whitespace() && ch != '>' && ch != '/' {
-                self.input.next();
-            } else {
-                let name = self.input.sub_str_from_cursor(cursor);
-                if name.len() == 0 {
-                    panic!("missing element name");
-                }
-                // subsequent calls to must return attributes from this property
-                self.prop = true;
-                return Some(XmlEvent::PushElement { name });
-            }
+        // '<' was already consumed
+        self.start = self.input.offset_from_source_str() - 1;
+        self.skip_name(|b| b == b'>' || b == b'/')?;
+
+        let name = self.input.sub_str_from_cursor(cursor);
+        if name.is_empty() {
+            panic!("missing element name at {}", self.current_position());
         }
-        None
+        // subsequent calls to must return attributes from this property
+        self.prop = true;
+        Some(XmlEvent::PushElement { name })
     }
 
     fn pop_element(&mut self) -> Option<XmlEvent<'a>> {
         let cursor = self.input.cursor();
-        while let Some(ch) = self.input.head() {
-            if !ch.is_whitespace() && ch != '>' {
-                self.input.next();
-            } else {
-                let name = Some(self.input.sub_str_from_cursor(cursor));
+        self.skip_name(|b| b == b'>')?;
 
-                self.ignore_whitespace();
-                match self.input.head() {
-                    Some('>') => {
-                        // consume '>'
-                        self.input.next();
-                    }
-                    Some(ch) => {
-                        panic!("unexpected char `{}` (\\u{:X})", ch, ch as u32);
-                    }
-                    None => panic!("unexpected end of file"),
-                }
+        let name = Some(self.input.sub_str_from_cursor(cursor));
 
-                return Some(XmlEvent::PopElement { name });
+        self.ignore_whitespace();
+        match self.input.head() {
+            Some('>') => {
+                // consume '>'
+                self.input.advance(1);
             }
+            Some(ch) => {
+                panic!(
+                    "unexpected char `{}` (\\u{:X}) at {}",
+                    ch,
+                    ch as u32,
+                    self.current_position()
+                );
+            }
+            None => panic!("unexpected end of file at {}", self.current_position()),
         }
-        None
+
+        Some(XmlEvent::PopElement { name })
     }
 
     fn element_events(&mut self) -> Option<XmlEvent<'a>> {
         loop {
             self.ignore_whitespace();
 
-            match self.input.head() {
-                Some('<') => {
+            match self.input.head_byte() {
+                Some(b'<') => {
                     // consume '<'
-                    self.input.next();
+                    self.input.advance(1);
                     match self.input.head() {
-                        Some('!') => self.ignore_comment(),
-                        None => panic!("unexpected end of file"),
-                        Some(ch) => panic!("unexpected char `{}` (\\u{:X})", ch, ch as u32),
+                        Some('!') => {
+                            let text = self.comment();
+                            if self.comments {
+                                return Some(XmlEvent::Comment { text });
+                            }
+                        }
+                        None => panic!("unexpected end of file at {}", self.current_position()),
+                        Some(ch) => panic!(
+                            "unexpected char `{}` (\\u{:X}) at {}",
+                            ch,
+                            ch as u32,
+                            self.current_position()
+                        ),
                     }
                 }
-                Some('>') => {
+                Some(b'>') => {
                     // consume '>'
-                    self.input.next();
+                    self.input.advance(1);
                     // resume document level events
                     self.prop = false;
+                    if self.start_tag_end {
+                        return Some(XmlEvent::StartTagEnd {
+                            self_closing: false,
+                        });
+                    }
                     return self.document_events();
                 }
-                Some('/') => {
+                Some(b'/') => {
                     // consume '/'
-                    self.input.next();
+                    self.input.advance(1);
                     match self.input.head() {
                         Some('>') => {
                             // consume '>'
-                            self.input.next();
+                            self.input.advance(1);
                             // resume document level events
                             self.prop = false;
+                            if self.start_tag_end {
+                                self.pending_pop = true;
+                                return Some(XmlEvent::StartTagEnd { self_closing: true });
+                            }
                             return Some(XmlEvent::PopElement { name: None });
                         }
                         None => {
-                            panic!("unexpected end of file");
+                            panic!("unexpected end of file at {}", self.current_position());
                         }
                         Some(ch) => {
-                            panic!("unexpected char `{}` (\\u{:X})", ch, ch as u32);
+                            panic!(
+                                "unexpected char `{}` (\\u{:X}) at {}",
+                                ch,
+                                ch as u32,
+                                self.current_position()
+                            );
                         }
                     }
                 }
@@ -242,76 +602,78 @@ impl<'a> XmlIter<'a> {
 
     fn push_attr(&mut self) -> Option<XmlEvent<'a>> {
         // attribute name
-        let name;
         let cursor = self.input.cursor();
-        loop {
-            if let Some(ch) = self.input.head() {
-                if !ch.is_whitespace() && ch != '=' && ch != '>' && ch != '/' {
-                    self.input.next();
-                } else {
-                    name = self.input.sub_str_from_cursor(cursor);
-                    if name.len() == 0 {
-                        panic!("missing attribute name");
-                    }
-                    break;
-                }
-            } else {
-                panic!("unexpected end of file");
-            }
+        if self
+            .skip_name(|b| b == b'=' || b == b'>' || b == b'/')
+            .is_none()
+            && !self.fragment
+        {
+            panic!("unexpected end of file at {}", self.current_position());
+        }
+        let name = self.input.sub_str_from_cursor(cursor);
+        if name.is_empty() {
+            panic!("missing attribute name at {}", self.current_position());
         }
 
         self.ignore_whitespace();
 
-        if self.input.head() != Some('=') {
+        if self.input.head_byte() != Some(b'=') {
             // attribute has no value
             return Some(XmlEvent::Attr { name, value: None });
         }
 
         // consume '='
-        self.input.next();
+        self.input.advance(1);
         self.ignore_whitespace();
 
         // expect and consume '\"'
         match self.input.head() {
             Some('\"') => {
-                self.input.next();
+                self.input.advance(1);
             }
-            None => panic!("unexpected end of file"),
-            Some(ch) => panic!("unexpected char `{}` (\\u{:X})", ch, ch as u32),
+            None => panic!("unexpected end of file at {}", self.current_position()),
+            Some(ch) => panic!(
+                "unexpected char `{}` (\\u{:X}) at {}",
+                ch,
+                ch as u32,
+                self.current_position()
+            ),
         }
 
         // attribute value
         let value;
         let cursor = self.input.cursor();
         loop {
-            match self.input.head() {
-                Some('\"') => {
+            match self.input.skip_until2(b'\"', b'\\') {
+                Some(b'\"') => {
                     value = Some(self.input.sub_str_from_cursor(cursor));
-                    self.input.next();
+                    self.input.advance(1);
                     break;
                 }
-                Some('\\') => {
+                Some(_) => {
                     // consume '\\'
-                    self.input.next();
+                    self.input.advance(1);
                     // ignore scaped char, any escaped utf8 chars in the format '\uXXXX' should be covered
                     self.input.next();
                 }
                 None => {
-                    panic!("unexpected end of file");
-                }
-                _ => {
-                    // keep reading string
-                    self.input.next();
+                    panic!("unexpected end of file at {}", self.current_position());
                 }
             }
         }
 
-        return Some(XmlEvent::Attr { name, value });
+        Some(XmlEvent::Attr { name, value })
     }
 }
 
+/// Same as [`char::is_whitespace`] for ASCII bytes
+#[inline(always)]
+fn is_ascii_whitespace(byte: u8) -> bool {
+    matches!(byte, b' ' | b'\t' | b'\n' | b'\r' | 0x0B | 0x0C)
+}
+
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use std::{
         alloc::{GlobalAlloc, Layout, System},
         sync::atomic::{AtomicUsize, Ordering},
@@ -321,7 +683,7 @@ mod tests {
 
     struct Allocator;
 
-    static ALLOCATIONS_COUNT: AtomicUsize = AtomicUsize::new(0);
+    pub(crate) static ALLOCATIONS_COUNT: AtomicUsize = AtomicUsize::new(0);
 
     unsafe impl GlobalAlloc for Allocator {
         unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
@@ -448,6 +810,55 @@ mod tests {
             .copied(),
         );
 
+        cmp(
+            XmlIter::from("<!--a--><r <!-- b --> ><!-- c --></r><!-- d").with_comments(true),
+            [
+                XmlEvent::Comment { text: "a" },
+                XmlEvent::PushElement { name: "r" },
+                XmlEvent::Comment { text: " b " },
+                XmlEvent::Comment { text: " c " },
+                XmlEvent::PopElement { name: Some("r") },
+                XmlEvent::Comment { text: " d" },
+            ]
+            .iter()
+            .copied(),
+        );
+
+        assert_eq!(ALLOCATIONS_COUNT.load(Ordering::Relaxed), 0, "allocated");
+    }
+
+    #[test]
+    fn start_tag_end() {
+        // reset allocations
+        ALLOCATIONS_COUNT.store(0, Ordering::Relaxed);
+
+        cmp(
+            XmlIter::from("<r x <!-- c --> ><a/>text</r>").with_start_tag_end(true),
+            [
+                XmlEvent::PushElement { name: "r" },
+                XmlEvent::Attr {
+                    name: "x",
+                    value: None,
+                },
+                XmlEvent::StartTagEnd {
+                    self_closing: false,
+                },
+                XmlEvent::PushElement { name: "a" },
+                XmlEvent::StartTagEnd { self_closing: true },
+                XmlEvent::PopElement { name: None },
+                XmlEvent::Text { text: "text" },
+                XmlEvent::PopElement { name: Some("r") },
+            ]
+            .iter()
+            .copied(),
+        );
+
+        let data = include_str!("../benches/files/medium.svg");
+        assert!(XmlIter::from(data)
+            .with_start_tag_end(true)
+            .filter(|evn| !matches!(evn, XmlEvent::StartTagEnd { .. }))
+            .eq(XmlIter::from(data)));
+
         assert_eq!(ALLOCATIONS_COUNT.load(Ordering::Relaxed), 0, "allocated");
     }
 
@@ -582,6 +993,19 @@ mod tests {
             .copied(),
         );
 
+        cmp(
+            XmlIter::from("<a>C:\\</a><b/>"),
+            [
+                XmlEvent::PushElement { name: "a" },
+                XmlEvent::Text { text: "C:\\" },
+                XmlEvent::PopElement { name: Some("a") },
+                XmlEvent::PushElement { name: "b" },
+                XmlEvent::PopElement { name: None },
+            ]
+            .iter()
+            .copied(),
+        );
+
         // doesnt support embedding '<' or '>' during the texts
         cmp(
             XmlIter::from("<a>20 &lt; 30</a>"),
@@ -688,4 +1112,150 @@ mod tests {
 
         assert_eq!(ALLOCATIONS_COUNT.load(Ordering::Relaxed), 0, "allocated");
     }
+
+    #[test]
+    fn skip_element() {
+        // reset allocations
+        ALLOCATIONS_COUNT.store(0, Ordering::Relaxed);
+
+        let mut iter = XmlIter::from("<r><a x=\"</a>\"><!-- </a> --><b/>text</a><c/></r>");
+        assert_eq!(iter.next(), Some(XmlEvent::PushElement { name: "r" }));
+        assert_eq!(iter.next(), Some(XmlEvent::PushElement { name: "a" }));
+        assert_eq!(
+            iter.skip_element(),
+            "<a x=\"</a>\"><!-- </a> --><b/>text</a>"
+        );
+        cmp(
+            iter,
+            [
+                XmlEvent::PushElement { name: "c" },
+                XmlEvent::PopElement { name: None },
+                XmlEvent::PopElement { name: Some("r") },
+            ]
+            .iter()
+            .copied(),
+        );
+
+        let mut iter = XmlIter::from("<r><a min=\"0\" /><b/></r>");
+        iter.next();
+        iter.next();
+        assert_eq!(
+            iter.next(),
+            Some(XmlEvent::Attr {
+                name: "min",
+                value: Some("0")
+            })
+        );
+        assert_eq!(iter.skip_element(), "<a min=\"0\" />");
+        assert_eq!(iter.next(), Some(XmlEvent::PushElement { name: "b" }));
+
+        assert_eq!(ALLOCATIONS_COUNT.load(Ordering::Relaxed), 0, "allocated");
+    }
+
+    #[test]
+    fn inner_outer_xml() {
+        // reset allocations
+        ALLOCATIONS_COUNT.store(0, Ordering::Relaxed);
+
+        let mut iter = XmlIter::from("<r><svg w=\"1\">\n  <path d=\"M 0 0\"/>\n</svg><e /></r>");
+        iter.next();
+        assert_eq!(iter.next(), Some(XmlEvent::PushElement { name: "svg" }));
+        assert_eq!(iter.inner_xml(), "\n  <path d=\"M 0 0\"/>\n");
+        iter.next();
+        // still works after consuming attributes
+        assert_eq!(
+            iter.outer_xml(),
+            "<svg w=\"1\">\n  <path d=\"M 0 0\"/>\n</svg>"
+        );
+        assert_eq!(iter.next(), Some(XmlEvent::PushElement { name: "path" }));
+        assert_eq!(iter.inner_xml(), "");
+        assert_eq!(iter.outer_xml(), "<path d=\"M 0 0\"/>");
+
+        // unclosed elements go up to the end of the input
+        let mut iter = XmlIter::from("<a><b>text");
+        iter.next();
+        assert_eq!(iter.inner_xml(), "<b>text");
+        assert_eq!(iter.outer_xml(), "<a><b>text");
+
+        assert_eq!(ALLOCATIONS_COUNT.load(Ordering::Relaxed), 0, "allocated");
+    }
+
+    #[test]
+    fn skip_every_element() {
+        let data = include_str!("../benches/files/medium.svg");
+        let events: Vec<_> = XmlIter::from(data).collect();
+
+        // skipping any element must resume right after its matching `PopElement`
+        for (i, evn) in events.iter().enumerate() {
+            if !matches!(evn, XmlEvent::PushElement { name } if !name.starts_with('?')) {
+                continue;
+            }
+
+            let mut depth = 0;
+            let pop = i + events[i..]
+                .iter()
+                .position(|evn| {
+                    match evn {
+                        XmlEvent::PushElement { .. } => depth += 1,
+                        XmlEvent::PopElement { .. } => depth -= 1,
+                        _ => {}
+                    }
+                    depth == 0
+                })
+                .unwrap();
+
+            let mut iter = XmlIter::from(data);
+            for _ in 0..=i {
+                iter.next();
+            }
+            let skipped = iter.skip_element();
+            assert!(skipped.starts_with('<') && skipped.ends_with('>'));
+            assert!(iter.eq(events[pop + 1..].iter().copied()));
+        }
+    }
+
+    #[test]
+    fn checkpoints() {
+        // reset allocations
+        ALLOCATIONS_COUNT.store(0, Ordering::Relaxed);
+
+        let data = include_str!("../benches/files/fonts.conf");
+        let mut iter = XmlIter::from(data).with_start_tag_end(true);
+        assert!(iter
+            .clone()
+            .eq(XmlIter::from(data).with_start_tag_end(true)));
+
+        let mut count = 0;
+        while let Some(peeked) = iter.peek() {
+            let checkpoint = iter.checkpoint();
+            assert_eq!(iter.next(), Some(peeked));
+            // speculatively read a few events and go back
+            let mut ahead = [None; 3];
+            ahead.fill_with(|| iter.next());
+            iter.restore(checkpoint);
+            assert_eq!(iter.next(), Some(peeked));
+            assert_eq!(
+                ahead,
+                [iter.peek(), iter.clone().nth(1), iter.clone().nth(2)]
+            );
+            count += 1;
+        }
+        assert_eq!(count, XmlIter::from(data).with_start_tag_end(true).count());
+
+        assert_eq!(ALLOCATIONS_COUNT.load(Ordering::Relaxed), 0, "allocated");
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn owned_events() {
+        let data = include_str!("../benches/files/fonts.conf");
+        let owned: Vec<OwnedXmlEvent> = XmlIter::from(data)
+            .with_comments(true)
+            .map(|evn| evn.to_owned())
+            .collect();
+        assert!(owned
+            .iter()
+            .map(OwnedXmlEvent::as_event)
+            .eq(XmlIter::from(data).with_comments(true)));
+    }
 }
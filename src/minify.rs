@@ -0,0 +1,134 @@
+use core::fmt::{self, Write};
+
+use crate::{XmlIter, XmlWriter};
+
+/// Sizes in bytes before and after [`minify`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MinifyStats {
+    pub input_len: usize,
+    pub output_len: usize,
+}
+
+impl MinifyStats {
+    /// Bytes removed by the minifier
+    #[inline(always)]
+    pub fn saved(&self) -> usize {
+        self.input_len.saturating_sub(self.output_len)
+    }
+}
+
+/// Writes the smallest document with the same events of `src`, without allocating
+///
+/// Comments, indentation and whitespace between tags and attributes are removed, even the
+/// space after a quoted value, texts are kept as the parser sees them, trimmed and split at
+/// new lines.
+///
+/// ```rust
+/// let mut out = String::new();
+/// let stats = xml1::minify("<r>\n  <!-- note -->\n  <a x = \"1\" />\n</r>\n", &mut out)?;
+/// assert_eq!(out, "<r><a x=\"1\"/></r>");
+/// assert_eq!(stats.saved(), 24);
+/// # Ok::<(), std::fmt::Error>(())
+/// ```
+pub fn minify(src: &str, out: &mut impl Write) -> Result<MinifyStats, fmt::Error> {
+    let mut writer = XmlWriter::new(Counter { out, len: 0 }).tight();
+    writer.write_all(XmlIter::from(src))?;
+    let counter = writer.finish()?;
+    Ok(MinifyStats {
+        input_len: src.len(),
+        output_len: counter.len,
+    })
+}
+
+struct Counter<W> {
+    out: W,
+    len: usize,
+}
+
+impl<W: Write> Write for Counter<W> {
+    #[inline(always)]
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.len += s.len();
+        self.out.write_str(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::string::String;
+
+    use core::sync::atomic::Ordering;
+
+    use super::*;
+    use crate::tests::ALLOCATIONS_COUNT;
+
+    /// Discards everything
+    struct Sink;
+
+    impl Write for Sink {
+        fn write_str(&mut self, _: &str) -> fmt::Result {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn same_events() {
+        for src in [
+            include_str!("../benches/files/fonts.conf"),
+            include_str!("../benches/files/huge.xml"),
+            include_str!("../benches/files/large.plist"),
+            include_str!("../benches/files/medium.svg"),
+        ] {
+            let mut out = String::new();
+            let stats = minify(src, &mut out).unwrap();
+            assert_eq!(stats.output_len, out.len());
+            assert!(stats.saved() > 0);
+            assert!(XmlIter::from(out.as_str()).eq(XmlIter::from(src)));
+            // already minified
+            assert_eq!(minify(&out, &mut String::new()).unwrap().saved(), 0);
+        }
+    }
+
+    #[test]
+    fn texts() {
+        let mut out = String::new();
+        minify("<a>\n  some > text\n  in lines\n</a>", &mut out).unwrap();
+        assert_eq!(out, "<a>some > text\nin lines</a>");
+    }
+
+    #[test]
+    fn never_longer() {
+        for src in [
+            "<a x=\"1\"y/>",
+            "<a x=\"1\" y z=\"2\"/>",
+            "<a x=\"1\"<!-- c -->y/>",
+            "<a>C:\\</a>",
+            "<a>x\r\ny</a>",
+            "<?xml v=\"1\"?><a/>",
+            include_str!("../benches/files/medium.svg"),
+        ] {
+            let mut out = String::new();
+            let stats = minify(src, &mut out).unwrap();
+            assert!(out.len() <= src.len(), "{:?} became {:?}", src, out);
+            assert_eq!(stats.output_len, out.len());
+            assert!(XmlIter::from(out.as_str()).eq(XmlIter::from(src)));
+        }
+
+        let mut out = String::new();
+        minify("<a x=\"1\" y z=\"2\" w/>", &mut out).unwrap();
+        assert_eq!(out, "<a x=\"1\"y z=\"2\"w/>");
+    }
+
+    #[test]
+    fn no_allocations() {
+        let src = include_str!("../benches/files/medium.svg");
+
+        // reset allocations
+        ALLOCATIONS_COUNT.store(0, Ordering::Relaxed);
+
+        let stats = minify(src, &mut Sink).unwrap();
+        assert!(stats.saved() > 0);
+
+        assert_eq!(ALLOCATIONS_COUNT.load(Ordering::Relaxed), 0, "allocated");
+    }
+}
@@ -0,0 +1,83 @@
+use core::str::from_utf8_unchecked;
+use std::{fs::File, io, path::Path};
+
+use memmap2::Mmap;
+
+use crate::XmlIter;
+
+/// A file memory mapped and validated as UTF-8, events borrow directly from the mapping
+/// so the document is never copied into a `String`
+///
+/// ```rust,no_run
+/// # use xml1::MappedDocument;
+/// let doc = MappedDocument::open("huge.xml")?;
+/// for evn in doc.iter() {
+///     // do something with evn
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct MappedDocument {
+    map: Mmap,
+}
+
+impl MappedDocument {
+    /// Opens and maps the file at `path`
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        Self::from_file(&file)
+    }
+
+    /// Maps an already opened `file`, fails with [`io::ErrorKind::InvalidData`] if it isn't valid UTF-8
+    pub fn from_file(file: &File) -> io::Result<Self> {
+        // SAFETY: the mapping is only ever read, like any other mmap the file must not be
+        // truncated or modified by other processes while mapped
+        let map = unsafe { Mmap::map(file)? };
+        core::str::from_utf8(&map)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Ok(Self { map })
+    }
+
+    #[inline(always)]
+    pub fn as_str(&self) -> &str {
+        // SAFETY: validated during construction and the mapping is read only
+        unsafe { from_utf8_unchecked(&self.map) }
+    }
+
+    /// Iterates over the events of the document
+    #[inline(always)]
+    pub fn iter(&self) -> XmlIter<'_> {
+        XmlIter::from(self.as_str())
+    }
+}
+
+impl<'a> IntoIterator for &'a MappedDocument {
+    type Item = crate::XmlEvent<'a>;
+    type IntoIter = XmlIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_events_as_string() {
+        let path = "benches/files/fonts.conf";
+        let data = std::fs::read_to_string(path).unwrap();
+        let doc = MappedDocument::open(path).unwrap();
+        assert_eq!(doc.as_str(), data);
+        assert!(doc.iter().eq(XmlIter::from(data.as_str())));
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let path = std::env::temp_dir().join("xml1_mmap_invalid_utf8.xml");
+        std::fs::write(&path, b"<a>\xFF\xFE</a>").unwrap();
+        let err = MappedDocument::open(&path).err().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,150 @@
+//! Parallel parsing of large documents split by the children of the root element.
+//!
+//! The document is split into contiguous chunks, each starting right at the `'<'` of one
+//! of the root's children. At these points the parser doesn't carry any state, so parsing
+//! every chunk on its own yields exactly the same events as parsing the whole document.
+//!
+//! ```rust
+//! let src = "<list><item a=\"1\"/><item a=\"2\"/></list>";
+//! let events = xml1::par::par_events(src);
+//! assert!(events.into_iter().eq(xml1::XmlIter::from(src)));
+//! ```
+
+use alloc::vec::Vec;
+
+use rayon::prelude::*;
+
+use crate::{scan, XmlEvent, XmlIter};
+
+/// Chunks smaller than this aren't worth sending to another thread
+pub const DEFAULT_MIN_CHUNK_LEN: usize = 64 * 1024;
+
+/// Splits `src` into contiguous chunks of at least `min_len` bytes (except for the last one),
+/// cutting only right before one of the root's children.
+///
+/// `'<'` and `'>'` inside quoted attribute values and comments are skipped, declarations
+/// such as `<?xml ...?>` and `<!DOCTYPE ...>` before the root are ignored.
+/// Concatenating the chunks gives back `src`.
+pub fn split(src: &str, min_len: usize) -> Vec<&str> {
+    let bytes = src.as_bytes();
+    let mut chunks = Vec::new();
+    let mut chunk_start = 0;
+
+    for child in root_children(bytes) {
+        if child - chunk_start >= min_len {
+            chunks.push(&src[chunk_start..child]);
+            chunk_start = child;
+        }
+    }
+
+    chunks.push(&src[chunk_start..]);
+    chunks
+}
+
+/// Parses each chunk in parallel, returns the events of each chunk in order
+pub fn parse_chunks<'a>(chunks: &[&'a str]) -> Vec<Vec<XmlEvent<'a>>> {
+    chunks
+        .par_iter()
+        .map(|chunk| XmlIter::from(*chunk).collect())
+        .collect()
+}
+
+/// Parses `src` in parallel, returns the same events of [`XmlIter`] in order
+pub fn par_events(src: &str) -> Vec<XmlEvent<'_>> {
+    let min_len = DEFAULT_MIN_CHUNK_LEN.max(src.len() / (rayon::current_num_threads() * 4));
+    let chunks = parse_chunks(&split(src, min_len));
+    let mut events = Vec::with_capacity(chunks.iter().map(Vec::len).sum());
+    for chunk in chunks {
+        events.extend(chunk);
+    }
+    events
+}
+
+/// Offsets of the `'<'` of every child of the root element, except for the first one
+fn root_children(bytes: &[u8]) -> impl Iterator<Item = usize> + '_ {
+    let mut pos = root_content(bytes);
+    let mut first = true;
+    core::iter::from_fn(move || loop {
+        let start = pos? + scan::memchr(b'<', &bytes[pos?..])?;
+        let rest = &bytes[start + 1..];
+        if rest.starts_with(b"!--") {
+            pos = scan::comment_end(bytes, start + 4);
+        } else if rest.starts_with(b"/") {
+            // end of the root
+            return None;
+        } else {
+            pos = scan::element_bounds(bytes, start + 1)
+                .ok()
+                .map(|bounds| bounds.end);
+            if !first {
+                return Some(start);
+            }
+            first = false;
+        }
+    })
+}
+
+/// Offset past the start tag of the root element, `None` if it has no content
+fn root_content(bytes: &[u8]) -> Option<usize> {
+    let mut pos = 0;
+    loop {
+        pos += scan::memchr(b'<', &bytes[pos..])?;
+        let rest = &bytes[pos + 1..];
+        if rest.starts_with(b"!--") {
+            pos = scan::comment_end(bytes, pos + 4)?;
+        } else if rest.starts_with(b"?") || rest.starts_with(b"!") {
+            // declarations
+            pos = scan::tag_end(bytes, pos + 1)?.0;
+        } else {
+            let (content, self_closing) = scan::tag_end(bytes, pos + 1)?;
+            return if self_closing { None } else { Some(content) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILES: [&str; 4] = [
+        include_str!("../benches/files/fonts.conf"),
+        include_str!("../benches/files/huge.xml"),
+        include_str!("../benches/files/large.plist"),
+        include_str!("../benches/files/medium.svg"),
+    ];
+
+    #[test]
+    fn same_events_as_sequential() {
+        for src in FILES {
+            assert!(par_events(src).into_iter().eq(XmlIter::from(src)));
+        }
+    }
+
+    #[test]
+    fn every_child_in_its_own_chunk() {
+        for src in FILES {
+            let chunks = split(src, 0);
+            assert_eq!(chunks.concat(), src);
+            let events = parse_chunks(&chunks);
+            assert!(events.into_iter().flatten().eq(XmlIter::from(src)));
+        }
+
+        // huge.xml root has lots of children
+        assert!(split(FILES[1], 4 * 1024).len() > 10);
+    }
+
+    #[test]
+    fn skips_quotes_and_comments() {
+        let src =
+            "<?xml v=\"1\"?><!-- <a> --><r><a v=\"</r><b>\"/><!-- </r> --> text <b>\n<c/></b></r>";
+        assert_eq!(
+            split(src, 0),
+            [
+                "<?xml v=\"1\"?><!-- <a> --><r><a v=\"</r><b>\"/><!-- </r> --> text ",
+                "<b>\n<c/></b></r>"
+            ]
+        );
+        assert_eq!(split("<r/>", 0), ["<r/>"]);
+        assert_eq!(split("<r><a>", 0), ["<r><a>"]);
+    }
+}
@@ -0,0 +1,384 @@
+//! Queue of edits applied to the source in a single pass, a lighter alternative to the [`cst`](crate::cst).
+//!
+//! Elements are addressed by the byte offset of their `<`, like the ones found in an [`index`](crate::index)
+//! or computed with [`Span::of`] from the `name` of a `PushElement`.
+//!
+//! ```rust
+//! # use xml1::patch::{Patch, Span};
+//! let src = "<a>\n  <b x=\"1\" y=\"2\"/>\n</a>";
+//! let b = src.find("<b").unwrap();
+//! let mut patch = Patch::new(src);
+//! patch.set_attr_value(b, "x", "10")?;
+//! patch.remove_attr(b, "y")?;
+//! patch.insert_attr_after(b, Some("x"), "z", None)?;
+//! assert_eq!(patch.apply(), "<a>\n  <b x=\"10\" z/>\n</a>");
+//! # Ok::<(), xml1::patch::NoElement>(())
+//! ```
+
+use alloc::{borrow::Cow, string::String, vec::Vec};
+use core::fmt::{self, Write};
+
+use crate::{writer::write_escaped, XmlEvent, XmlIter};
+
+/// Byte range of the source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    #[inline(always)]
+    pub const fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// Span of `sub`, a slice returned by the parser, inside `src`
+    ///
+    /// # Panics
+    ///
+    /// If `sub` doesn't point inside `src`
+    pub fn of(src: &str, sub: &str) -> Self {
+        let start = (sub.as_ptr() as usize)
+            .checked_sub(src.as_ptr() as usize)
+            .filter(|&start| start + sub.len() <= src.len())
+            .expect("slice isn't part of the source");
+        Self::new(start, start + sub.len())
+    }
+
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+/// Error of the attribute edits of a [`Patch`], the offset isn't at the `<` of an element
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoElement {
+    pub offset: usize,
+}
+
+impl fmt::Display for NoElement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no element at {}", self.offset)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Edit<'p> {
+    span: Span,
+    text: Cow<'p, str>,
+}
+
+/// Spans of an attribute
+#[derive(Debug, Clone, Copy)]
+struct AttrSpan {
+    name: Span,
+    /// Without the quotes
+    value: Option<Span>,
+}
+
+impl AttrSpan {
+    /// End of the attribute, past the closing quote
+    fn end(&self) -> usize {
+        self.value.map_or(self.name.end, |value| value.end + 1)
+    }
+}
+
+/// Edits queued over a source, see the [module docs](self)
+#[derive(Debug, Clone)]
+pub struct Patch<'p> {
+    src: &'p str,
+    edits: Vec<Edit<'p>>,
+}
+
+impl<'p> Patch<'p> {
+    pub fn new(src: &'p str) -> Self {
+        Self {
+            src,
+            edits: Vec::new(),
+        }
+    }
+
+    #[inline(always)]
+    pub fn source(&self) -> &'p str {
+        self.src
+    }
+
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.edits.len()
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.edits.is_empty()
+    }
+
+    /// Replaces the `span` with `text`, an empty span inserts
+    pub fn replace(&mut self, span: Span, text: impl Into<Cow<'p, str>>) {
+        assert!(
+            span.start <= span.end
+                && self.src.is_char_boundary(span.start)
+                && self.src.is_char_boundary(span.end),
+            "invalid span"
+        );
+        self.edits.push(Edit {
+            span,
+            text: text.into(),
+        });
+    }
+
+    /// Inserts `text` at `offset`, insertions at the same offset are applied in order
+    pub fn insert(&mut self, offset: usize, text: impl Into<Cow<'p, str>>) {
+        self.replace(Span::new(offset, offset), text);
+    }
+
+    pub fn remove(&mut self, span: Span) {
+        self.replace(span, "");
+    }
+
+    /// Replaces the value of the attribute `name` of the element at `element`, a valueless
+    /// attribute gets one. The raw `value` gets its unescaped `'"'` escaped.
+    ///
+    /// Returns `false` if the element doesn't have the attribute, [`NoElement`] if `element`
+    /// isn't the offset of the `<` of an element.
+    pub fn set_attr_value(
+        &mut self,
+        element: usize,
+        name: &str,
+        value: &str,
+    ) -> Result<bool, NoElement> {
+        let Some(attr) = self.attr(element, name)? else {
+            return Ok(false);
+        };
+        match attr.value {
+            Some(span) => self.replace(span, escaped(value)),
+            None => self.insert(attr.name.end, ["=\"", &escaped(value), "\""].concat()),
+        }
+        Ok(true)
+    }
+
+    /// Removes the attribute `name` of the element at `element` along with the whitespace before it
+    ///
+    /// Returns `false` if the element doesn't have the attribute, [`NoElement`] if `element`
+    /// isn't the offset of the `<` of an element.
+    pub fn remove_attr(&mut self, element: usize, name: &str) -> Result<bool, NoElement> {
+        let Some(attr) = self.attr(element, name)? else {
+            return Ok(false);
+        };
+        let start = self.src[..attr.name.start].trim_end().len();
+        self.remove(Span::new(start, attr.end()));
+        Ok(true)
+    }
+
+    /// Inserts the attribute `name` to the element at `element` after the attribute `after`,
+    /// or before the first one when `after` is `None`. The raw `value` gets its unescaped `'"'` escaped.
+    ///
+    /// Returns `false` if the element doesn't have the attribute `after`, [`NoElement`] if `element`
+    /// isn't the offset of the `<` of an element.
+    pub fn insert_attr_after(
+        &mut self,
+        element: usize,
+        after: Option<&str>,
+        name: &str,
+        value: Option<&str>,
+    ) -> Result<bool, NoElement> {
+        let offset = match after {
+            Some(after) => match self.attr(element, after)? {
+                Some(attr) => attr.end(),
+                None => return Ok(false),
+            },
+            None => element + 1 + self.start_tag(element)?.0.len(),
+        };
+        let text = match value {
+            Some(value) => [" ", name, "=\"", &escaped(value), "\""].concat(),
+            None => [" ", name].concat(),
+        };
+        self.insert(offset, text);
+        Ok(true)
+    }
+
+    /// Writes the patched source
+    ///
+    /// # Panics
+    ///
+    /// If any two edits overlap, insertions at the start or end of a replacement don't
+    pub fn apply_to(&self, out: &mut impl Write) -> fmt::Result {
+        let mut order: Vec<usize> = (0..self.edits.len()).collect();
+        // stable, so insertions at the same offset keep their order
+        order.sort_by_key(|&i| (self.edits[i].span.start, self.edits[i].span.end));
+
+        let mut prev = 0;
+        let mut last: Option<Span> = None;
+        for i in order {
+            let edit = &self.edits[i];
+            match last {
+                Some(last) if edit.span.start < last.end => {
+                    panic!("edits {:?} and {:?} overlap", last, edit.span)
+                }
+                _ => {}
+            }
+            out.write_str(&self.src[prev..edit.span.start])?;
+            out.write_str(&edit.text)?;
+            prev = edit.span.end;
+            last = Some(edit.span);
+        }
+        out.write_str(&self.src[prev..])
+    }
+
+    /// Returns the patched source, see [`Patch::apply_to`]
+    pub fn apply(&self) -> String {
+        let mut out = String::with_capacity(self.src.len());
+        self.apply_to(&mut out).unwrap();
+        out
+    }
+
+    /// Parses the start tag of the element at `element`, returns its name and the iterator
+    /// right after it
+    fn start_tag(&self, element: usize) -> Result<(&'p str, XmlIter<'p>), NoElement> {
+        let error = NoElement { offset: element };
+        let src = self.src.get(element..).ok_or(error)?;
+        let mut iter = XmlIter::from(src);
+        match iter.next() {
+            // the parser skips comments and texts, the name must follow the `<`
+            Some(XmlEvent::PushElement { name }) if Span::of(src, name).start == 1 => {
+                Ok((name, iter))
+            }
+            _ => Err(error),
+        }
+    }
+
+    fn attr(&self, element: usize, name: &str) -> Result<Option<AttrSpan>, NoElement> {
+        let span = |s: &str| {
+            let span = Span::of(&self.src[element..], s);
+            Span::new(element + span.start, element + span.end)
+        };
+        Ok(self
+            .start_tag(element)?
+            .1
+            .map_while(|evn| match evn {
+                XmlEvent::Attr { name, value } => Some((name, value)),
+                _ => None,
+            })
+            .find(|&(attr, _)| attr == name)
+            .map(|(name, value)| AttrSpan {
+                name: span(name),
+                value: value.map(span),
+            }))
+    }
+}
+
+fn escaped(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    write_escaped(&mut text, value, b'"').unwrap();
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attrs() {
+        let src = "<r>\n  <w a=\"1\"  b\n     c = \"x\\\"y\"/>\n</r>";
+        let w = src.find("<w").unwrap();
+
+        let mut patch = Patch::new(src);
+        assert!(patch.set_attr_value(w, "a", "say \"hi\"").unwrap());
+        assert!(patch.set_attr_value(w, "b", "2").unwrap());
+        assert!(!patch.set_attr_value(w, "missing", "2").unwrap());
+        assert_eq!(
+            patch.apply(),
+            "<r>\n  <w a=\"say \\\"hi\\\"\"  b=\"2\"\n     c = \"x\\\"y\"/>\n</r>"
+        );
+
+        let mut patch = Patch::new(src);
+        assert!(patch.remove_attr(w, "c").unwrap());
+        assert!(patch.remove_attr(w, "a").unwrap());
+        assert!(patch
+            .insert_attr_after(w, None, "first", Some("0"))
+            .unwrap());
+        assert!(patch
+            .insert_attr_after(w, Some("b"), "after", None)
+            .unwrap());
+        assert!(patch
+            .insert_attr_after(w, Some("b"), "after2", None)
+            .unwrap());
+        assert_eq!(
+            patch.apply(),
+            "<r>\n  <w first=\"0\"  b after after2/>\n</r>"
+        );
+    }
+
+    #[test]
+    fn no_element() {
+        let src = "<r><!-- c --><a x=\"1\"/>text</r>";
+        let mut patch = Patch::new(src);
+        for offset in [
+            src.find("<!--").unwrap(),
+            src.find("text").unwrap(),
+            src.find("</r").unwrap(),
+            src.len() + 1,
+        ] {
+            assert_eq!(
+                patch.set_attr_value(offset, "x", "2"),
+                Err(NoElement { offset })
+            );
+        }
+        assert!(patch.is_empty());
+        assert!(patch.remove_attr(src.find("<a").unwrap(), "x").unwrap());
+    }
+
+    #[test]
+    fn spans() {
+        let src = "<a>text</a>";
+        let Some(XmlEvent::Text { text }) = XmlIter::from(src).nth(1) else {
+            panic!()
+        };
+        let span = Span::of(src, text);
+        assert_eq!(span, Span::new(3, 7));
+
+        let mut patch = Patch::new(src);
+        patch.insert(span.end, "!");
+        patch.replace(span, "other");
+        patch.insert(span.start, "<b/>");
+        assert_eq!(patch.apply(), "<a><b/>other!</a>");
+    }
+
+    #[test]
+    #[should_panic]
+    fn overlap() {
+        let mut patch = Patch::new("<a>text</a>");
+        patch.replace(Span::new(3, 6), "x");
+        patch.replace(Span::new(5, 7), "y");
+        patch.apply();
+    }
+
+    #[test]
+    fn huge() {
+        let src = include_str!("../benches/files/huge.xml");
+        let mut patch = Patch::new(src);
+        let mut elements = 0;
+        for evn in XmlIter::from(src) {
+            if let XmlEvent::PushElement { name } = evn {
+                let element = Span::of(src, name).start - 1;
+                if patch.remove_attr(element, "count").unwrap() {
+                    elements += 1;
+                }
+            }
+        }
+        assert!(elements > 0);
+        let out = patch.apply();
+        assert!(out.len() < src.len());
+        let count = XmlIter::from(out.as_str())
+            .filter(|evn| matches!(evn, XmlEvent::Attr { name: "count", .. }))
+            .count();
+        assert_eq!(count, 0);
+    }
+}
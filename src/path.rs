@@ -0,0 +1,239 @@
+use crate::{XmlEvent, XmlIter};
+
+/// Only yields the events of the elements whose path matches a pattern, without allocating
+///
+/// The pattern is a list of element names separated by `/`, from the top level element.
+/// A `*` matches any single name and `**` any number of names, including none.
+/// Every event of a matching element is yielded, from its `PushElement` to its `PopElement`.
+/// Declarations like `<?xml ...?>` are not part of the path and are never yielded.
+///
+/// Up to `N` names are kept, deeper elements are only yielded when inside a matching one.
+///
+/// ```rust
+/// # use xml1::{PathFilter, XmlEvent, XmlIter};
+/// let src = "<plist><dict><array><string>a</string></array></dict><array/></plist>";
+/// let texts: Vec<_> = PathFilter::new(XmlIter::from(src), "plist/dict/array")
+///     .filter_map(|evn| match evn {
+///         XmlEvent::Text { text } => Some(text),
+///         _ => None,
+///     })
+///     .collect();
+/// assert_eq!(texts, ["a"]);
+/// ```
+pub struct PathFilter<'a, 'p, const N: usize = 32> {
+    iter: XmlIter<'a>,
+    pattern: &'p str,
+    stack: [&'a str; N],
+    /// Number of open elements, can be larger than `N`
+    depth: usize,
+    /// Depth of the matching element being yielded, zero if none
+    matched: usize,
+    /// Inside the start tag of a declaration
+    declaration: bool,
+}
+
+impl<'a, 'p> PathFilter<'a, 'p> {
+    pub fn new(iter: XmlIter<'a>, pattern: &'p str) -> Self {
+        Self::with_depth(iter, pattern)
+    }
+}
+
+impl<'a, 'p, const N: usize> PathFilter<'a, 'p, N> {
+    /// Same as [`PathFilter::new`] but keeps up to `N` names
+    pub fn with_depth(iter: XmlIter<'a>, pattern: &'p str) -> Self {
+        Self {
+            iter,
+            pattern: pattern.strip_prefix('/').unwrap_or(pattern),
+            stack: [""; N],
+            depth: 0,
+            matched: 0,
+            declaration: false,
+        }
+    }
+
+    /// Names of the open elements, from the top level one
+    #[inline(always)]
+    pub fn current_path(&self) -> &[&'a str] {
+        &self.stack[..self.depth.min(N)]
+    }
+
+    pub fn into_inner(self) -> XmlIter<'a> {
+        self.iter
+    }
+}
+
+impl<'a, const N: usize> Iterator for PathFilter<'a, '_, N> {
+    type Item = XmlEvent<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let evn = self.iter.next()?;
+            if self.declaration {
+                // declarations are closed by the end of their start tag
+                match evn {
+                    XmlEvent::Attr { .. } | XmlEvent::StartTagEnd { .. } => continue,
+                    XmlEvent::PopElement { name: None } => {
+                        self.declaration = false;
+                        continue;
+                    }
+                    _ => self.declaration = false,
+                }
+            }
+            if let Some(evn) = self.event(evn) {
+                return Some(evn);
+            }
+        }
+    }
+}
+
+impl<'a, const N: usize> PathFilter<'a, '_, N> {
+    fn event(&mut self, evn: XmlEvent<'a>) -> Option<XmlEvent<'a>> {
+        match evn {
+            XmlEvent::PushElement { name } => {
+                if name.starts_with('?') || name.starts_with('!') {
+                    self.declaration = true;
+                    return None;
+                }
+                if self.depth < N {
+                    self.stack[self.depth] = name;
+                }
+                self.depth += 1;
+                if self.matched == 0
+                    && self.depth <= N
+                    && matches(self.pattern, self.current_path())
+                {
+                    self.matched = self.depth;
+                }
+            }
+            XmlEvent::PopElement { .. } => {
+                // stray end tags are ignored
+                if self.depth == 0 {
+                    return None;
+                }
+                let yielding = self.matched != 0;
+                if self.depth == self.matched {
+                    self.matched = 0;
+                }
+                self.depth -= 1;
+                return yielding.then_some(evn);
+            }
+            _ => {}
+        }
+        (self.matched != 0).then_some(evn)
+    }
+}
+
+/// True if the `path` matches the whole `pattern`
+fn matches(pattern: &str, path: &[&str]) -> bool {
+    let (segment, rest) = match pattern.split_once('/') {
+        Some((segment, rest)) => (segment, Some(rest)),
+        None => (pattern, None),
+    };
+    if segment == "**" {
+        return match rest {
+            Some(rest) => (0..=path.len()).any(|i| matches(rest, &path[i..])),
+            None => true,
+        };
+    }
+    match (path.split_first(), rest) {
+        (Some((name, path)), rest) if segment == "*" || segment == *name => match rest {
+            Some(rest) => matches(rest, path),
+            None => path.is_empty(),
+        },
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use core::sync::atomic::Ordering;
+
+    use super::*;
+    use crate::tests::ALLOCATIONS_COUNT;
+
+    fn names<'a>(src: &'a str, pattern: &str) -> Vec<&'a str> {
+        PathFilter::new(XmlIter::from(src), pattern)
+            .filter_map(|evn| match evn {
+                XmlEvent::PushElement { name } => Some(name),
+                XmlEvent::Text { text } => Some(text),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn patterns() {
+        assert!(matches("a/b", &["a", "b"]));
+        assert!(!matches("a/b", &["a"]));
+        assert!(!matches("a/b", &["a", "b", "c"]));
+        assert!(matches("a/*/c", &["a", "b", "c"]));
+        assert!(matches("**", &[]));
+        assert!(matches("**/c", &["c"]));
+        assert!(matches("**/c", &["a", "b", "c"]));
+        assert!(matches("a/**/c", &["a", "c"]));
+        assert!(!matches("a/**/c", &["a", "c", "d"]));
+        assert!(matches("a/**", &["a", "c", "d"]));
+
+        let src = "<?xml version=\"1.0\"?><r><a>1<b>2</b></a><c><a>3</a></c></r>";
+        assert_eq!(names(src, "r/a"), ["a", "1", "b", "2"]);
+        assert_eq!(names(src, "/r/*/a"), ["a", "3"]);
+        assert_eq!(names(src, "**/a"), ["a", "1", "b", "2", "a", "3"]);
+        assert_eq!(names(src, "r/a/b"), ["b", "2"]);
+        assert_eq!(names(src, "a"), [] as [&str; 0]);
+
+        // declarations inside a matching element are skipped, so it stays balanced
+        for src in ["<r><?xml v=\"1\"?><a/></r>", "<r><!DOCTYPE r x><a/></r>"] {
+            let events: Vec<_> = PathFilter::new(XmlIter::from(src), "r").collect();
+            assert_eq!(
+                events,
+                [
+                    XmlEvent::PushElement { name: "r" },
+                    XmlEvent::PushElement { name: "a" },
+                    XmlEvent::PopElement { name: None },
+                    XmlEvent::PopElement { name: Some("r") },
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn balanced_and_no_allocations() {
+        let src = include_str!("../benches/files/large.plist");
+
+        ALLOCATIONS_COUNT.store(0, Ordering::Relaxed);
+        let mut filter = PathFilter::new(XmlIter::from(src), "plist/dict/array/dict/array");
+        let mut depth = 0;
+        let mut elements = 0;
+        while let Some(evn) = filter.next() {
+            match evn {
+                XmlEvent::PushElement { .. } => {
+                    depth += 1;
+                    elements += 1;
+                    assert!(filter
+                        .current_path()
+                        .starts_with(&["plist", "dict", "array"]));
+                }
+                XmlEvent::PopElement { .. } => depth -= 1,
+                _ => {}
+            }
+            assert!(depth >= 0);
+        }
+        assert_eq!(depth, 0);
+        assert!(elements > 0);
+        assert_eq!(ALLOCATIONS_COUNT.load(Ordering::Relaxed), 0, "allocated");
+    }
+
+    #[test]
+    fn deeper_than_capacity() {
+        let src = "<a><b><c><d>text</d></c></b></a>";
+        let mut filter = PathFilter::<'_, '_, 2>::with_depth(XmlIter::from(src), "a/b");
+        assert_eq!(filter.next(), Some(XmlEvent::PushElement { name: "b" }));
+        assert_eq!(filter.current_path(), ["a", "b"]);
+        assert_eq!(filter.by_ref().count(), 6);
+
+        let filter = PathFilter::<'_, '_, 2>::with_depth(XmlIter::from(src), "a/b/c");
+        assert_eq!(filter.count(), 0);
+    }
+}
@@ -0,0 +1,224 @@
+use core::{fmt, ops::Range};
+
+use crate::{scan, XmlIter};
+
+/// Location in a source, used for diagnostics
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    /// In bytes, from zero
+    pub offset: usize,
+    /// From one
+    pub line: usize,
+    /// In chars, from one
+    pub column: usize,
+}
+
+impl Position {
+    pub const START: Self = Self {
+        offset: 0,
+        line: 1,
+        column: 1,
+    };
+
+    /// Position of the byte `offset` of `src`
+    ///
+    /// ```rust
+    /// # use xml1::Position;
+    /// let pos = Position::of("<r>\n  <サ/>", 10);
+    /// assert_eq!((pos.line, pos.column), (2, 5));
+    /// ```
+    pub fn of(src: &str, offset: usize) -> Self {
+        Self::START.advanced(&src[..offset])
+    }
+
+    /// Position right after `s`, when `s` starts at `self`
+    fn advanced(self, s: &str) -> Self {
+        let bytes = s.as_bytes();
+        let mut line = self.line;
+        let mut line_start = None;
+        let mut pos = 0;
+        while let Some(i) = scan::memchr(b'\n', &bytes[pos..]) {
+            line += 1;
+            pos += i + 1;
+            line_start = Some(pos);
+        }
+        let column = match line_start {
+            Some(start) => s[start..].chars().count() + 1,
+            None => self.column + s.chars().count(),
+        };
+        Self {
+            offset: self.offset + s.len(),
+            line,
+            column,
+        }
+    }
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Self::START
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+impl<'a> XmlIter<'a> {
+    /// Parses `&outer[range]` alone, positions are still reported relative to `outer`
+    ///
+    /// ```rust
+    /// # use xml1::{XmlEvent, XmlIter};
+    /// let markdown = "# Example\n\n```xml\n<r x=\"1\"/>\n```\n";
+    /// let mut iter = XmlIter::from_sub_str(markdown, 18..28);
+    /// assert_eq!(iter.next(), Some(XmlEvent::PushElement { name: "r" }));
+    /// assert_eq!(iter.current_position().to_string(), "4:3");
+    /// ```
+    pub fn from_sub_str(outer: &'a str, range: Range<usize>) -> Self {
+        let base = Position::of(outer, range.start);
+        XmlIter::from(&outer[range]).with_base(base)
+    }
+
+    /// Parses a list of attributes alone, as if inside a start tag, like `a="1" b`
+    ///
+    /// Content fragments, like `text <b>bold</b>`, are parsed with [`XmlIter::from`]
+    ///
+    /// ```rust
+    /// # use xml1::{XmlEvent, XmlIter};
+    /// let mut iter = XmlIter::from_attrs("a=\"1\" b");
+    /// assert_eq!(iter.next(), Some(XmlEvent::Attr { name: "a", value: Some("1") }));
+    /// assert_eq!(iter.next(), Some(XmlEvent::Attr { name: "b", value: None }));
+    /// assert_eq!(iter.next(), None);
+    /// ```
+    pub fn from_attrs(src: &'a str) -> Self {
+        let mut iter = XmlIter::from(src);
+        iter.prop = true;
+        iter.fragment = true;
+        iter
+    }
+
+    /// Reports positions as if the source started at `base`, for sources embedded in
+    /// other files
+    pub fn with_base(mut self, base: Position) -> Self {
+        self.base = base;
+        self
+    }
+
+    /// Position of the next event, or where the parser stopped after an error, it has to
+    /// count the lines from the start of the source
+    pub fn current_position(&self) -> Position {
+        let src = self.input.source();
+        self.base
+            .advanced(&src[..self.input.offset_from_source_str()])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::string::ToString;
+
+    use super::*;
+    use crate::XmlEvent;
+
+    #[test]
+    fn positions() {
+        let src = "<r>\n  <a x=\"1\"/>\r\n\tサイボーグ\n</r>";
+        assert_eq!(Position::of(src, 0), Position::START);
+        assert_eq!(
+            Position::of(src, src.len()),
+            Position {
+                offset: src.len(),
+                line: 4,
+                column: 5
+            }
+        );
+
+        let mut iter = XmlIter::from(src);
+        let mut positions = std::vec::Vec::new();
+        while iter.next().is_some() {
+            positions.push(iter.current_position().to_string());
+        }
+        assert_eq!(positions, ["1:3", "2:5", "2:11", "2:13", "3:7", "4:5"]);
+    }
+
+    #[test]
+    fn embedded() {
+        let outer = "let xml = \"\n<r>\n  <a>\";";
+        let start = outer.find('<').unwrap();
+        let end = outer.rfind('"').unwrap();
+        let mut iter = XmlIter::from_sub_str(outer, start..end);
+        assert_eq!(iter.current_position(), Position::of(outer, start));
+        iter.nth(1);
+        assert_eq!(iter.current_position(), Position::of(outer, start + 8));
+        assert_eq!(iter.current_position().to_string(), "3:5");
+
+        let base = Position {
+            offset: 100,
+            line: 10,
+            column: 20,
+        };
+        let mut iter = XmlIter::from("<a/>").with_base(base);
+        iter.next();
+        assert_eq!(
+            iter.current_position(),
+            Position {
+                offset: 102,
+                line: 10,
+                column: 22
+            }
+        );
+    }
+
+    #[test]
+    fn attrs_fragment() {
+        let events: std::vec::Vec<_> = XmlIter::from_attrs(" a=\"1\" <!-- c --> b ").collect();
+        assert_eq!(
+            events,
+            [
+                XmlEvent::Attr {
+                    name: "a",
+                    value: Some("1")
+                },
+                XmlEvent::Attr {
+                    name: "b",
+                    value: None
+                },
+            ]
+        );
+        assert!(XmlIter::from_attrs("a b").eq([
+            XmlEvent::Attr {
+                name: "a",
+                value: None
+            },
+            XmlEvent::Attr {
+                name: "b",
+                value: None
+            },
+        ]));
+        assert_eq!(XmlIter::from_attrs("").next(), None);
+        // the fragment may also end its start tag
+        assert!(XmlIter::from_attrs("a>text").eq([
+            XmlEvent::Attr {
+                name: "a",
+                value: None
+            },
+            XmlEvent::Text { text: "text" },
+        ]));
+    }
+
+    #[test]
+    #[should_panic(expected = "unexpected end of file at 1:5")]
+    fn truncated_attr_name() {
+        XmlIter::from("<a x").for_each(drop);
+    }
+
+    #[test]
+    #[should_panic(expected = "unexpected char `=` (\\u3D) at 3:8")]
+    fn panics_with_position() {
+        let outer = "<r>\n  <a x=\"1\"\n  y =  =\"2\"/></r>";
+        let start = outer.find("<a").unwrap();
+        XmlIter::from_sub_str(outer, start..outer.len()).for_each(drop);
+    }
+}
@@ -0,0 +1,331 @@
+//! Pretty printer that re-indents documents.
+//!
+//! Every element, text and comment goes in its own line, except for elements with a
+//! single text that are kept inline like `<key>value</key>`. Attributes are separated by a
+//! single space and optionally wrapped one per line, as Inkscape does, when the start tag
+//! gets too long. Comments are preserved, formatting the output again doesn't change it.
+//!
+//! ```rust
+//! # use xml1::pretty::{format_to_string, FormatOptions};
+//! let src = "<r><!-- note --><a  x=\"1\"><key>value</key></a></r>";
+//! assert_eq!(
+//!     format_to_string(src, &FormatOptions::default()),
+//!     "<r>\n  <!-- note -->\n  <a x=\"1\">\n    <key>value</key>\n  </a>\n</r>\n"
+//! );
+//! ```
+
+use alloc::{string::String, vec::Vec};
+use core::{
+    fmt::{self, Write},
+    iter::Peekable,
+};
+
+use crate::{writer::write_escaped, XmlEvent, XmlIter};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// Spaces for each level of indentation
+    pub indent: usize,
+    /// Start tags wider than this have one attribute per line, never wraps when `None`
+    pub max_width: Option<usize>,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            indent: 2,
+            max_width: None,
+        }
+    }
+}
+
+/// Formats `src` into `out`
+pub fn format(src: &str, out: &mut impl Write, options: &FormatOptions) -> fmt::Result {
+    let mut printer = Printer {
+        out,
+        options,
+        depth: 0,
+        first_line: true,
+        items: Vec::new(),
+        comments: Vec::new(),
+    };
+    printer.run(XmlIter::from(src).with_comments(true).peekable())
+}
+
+/// Formats `src` into a new `String`
+pub fn format_to_string(src: &str, options: &FormatOptions) -> String {
+    let mut out = String::with_capacity(src.len());
+    format(src, &mut out, options).expect("a String never fails to format");
+    out
+}
+
+struct Printer<'o, 'a, W> {
+    out: &'o mut W,
+    options: &'o FormatOptions,
+    depth: usize,
+    first_line: bool,
+    /// Attributes and comments of the current start tag
+    items: Vec<XmlEvent<'a>>,
+    /// Comments right after the current start tag
+    comments: Vec<XmlEvent<'a>>,
+}
+
+impl<'a, W: Write> Printer<'_, 'a, W> {
+    fn run(&mut self, mut events: Peekable<XmlIter<'a>>) -> fmt::Result {
+        while let Some(evn) = events.next() {
+            match evn {
+                XmlEvent::PushElement { name } => self.element(name, &mut events)?,
+                XmlEvent::PopElement { name } => {
+                    self.depth = self.depth.saturating_sub(1);
+                    self.line()?;
+                    self.out.write_str("</")?;
+                    self.out.write_str(name.unwrap_or_default())?;
+                    self.out.write_char('>')?;
+                }
+                XmlEvent::Text { text } => {
+                    self.line()?;
+                    self.out.write_str(text)?;
+                }
+                XmlEvent::Comment { text } => {
+                    self.line()?;
+                    write_comment(self.out, text)?;
+                }
+                XmlEvent::Attr { .. } => unreachable!("attributes are consumed with their element"),
+                XmlEvent::StartTagEnd { .. } => unreachable!("not enabled"),
+            }
+        }
+        if !self.first_line {
+            self.out.write_char('\n')?;
+        }
+        Ok(())
+    }
+
+    fn element(&mut self, name: &'a str, events: &mut Peekable<XmlIter<'a>>) -> fmt::Result {
+        self.items.clear();
+        while let Some(evn @ (XmlEvent::Attr { .. } | XmlEvent::Comment { .. })) = events.peek() {
+            self.items.push(*evn);
+            events.next();
+        }
+
+        // `<?xml ... ?>` is parsed as an element named "?xml" with a "?" attribute
+        let declaration = name.starts_with('?') || name.starts_with('!');
+
+        let self_closing = matches!(events.peek(), Some(XmlEvent::PopElement { name: None }));
+        if self_closing {
+            events.next();
+        }
+
+        // the parser gives the same events for comments after the last attribute and
+        // comments at the start of the content, prefer the content unless self closing
+        self.comments.clear();
+        if !self_closing {
+            let last_attr = self
+                .items
+                .iter()
+                .rposition(|item| matches!(item, XmlEvent::Attr { .. }));
+            let split = last_attr.map_or(0, |i| i + 1);
+            self.comments.extend(self.items.drain(split..));
+        }
+
+        let question_mark = name.starts_with('?')
+            && matches!(
+                self.items.last(),
+                Some(XmlEvent::Attr {
+                    name: "?",
+                    value: None
+                })
+            );
+        if question_mark {
+            self.items.pop();
+        }
+
+        self.line()?;
+        self.out.write_char('<')?;
+        self.out.write_str(name)?;
+
+        let width = self.depth * self.options.indent
+            + 1
+            + name.chars().count()
+            + self
+                .items
+                .iter()
+                .map(|item| 1 + item_width(item))
+                .sum::<usize>()
+            + if self_closing { 2 } else { 1 };
+        let wrap = !self.items.is_empty()
+            && self
+                .options
+                .max_width
+                .is_some_and(|max_width| width > max_width);
+
+        for i in 0..self.items.len() {
+            if wrap {
+                self.out.write_char('\n')?;
+                self.indent(self.depth + 1)?;
+            } else {
+                self.out.write_char(' ')?;
+            }
+            match self.items[i] {
+                XmlEvent::Attr { name, value } => {
+                    self.out.write_str(name)?;
+                    if let Some(value) = value {
+                        self.out.write_str("=\"")?;
+                        write_escaped(self.out, value, b'"')?;
+                        self.out.write_char('"')?;
+                    }
+                }
+                XmlEvent::Comment { text } => write_comment(self.out, text)?,
+                _ => unreachable!(),
+            }
+        }
+
+        if question_mark {
+            self.out.write_char('?')?;
+        }
+        if self_closing {
+            return self.out.write_str("/>");
+        }
+        self.out.write_char('>')?;
+
+        if !self.comments.is_empty() {
+            if !declaration {
+                self.depth += 1;
+            }
+            for i in 0..self.comments.len() {
+                if let XmlEvent::Comment { text } = self.comments[i] {
+                    self.line()?;
+                    write_comment(self.out, text)?;
+                }
+            }
+            return Ok(());
+        }
+
+        // keep empty elements and elements with a single text inline
+        if let Some(XmlEvent::Text { text }) = events.peek() {
+            let text = *text;
+            events.next();
+            if let Some(XmlEvent::PopElement { name: Some(end) }) = events.peek() {
+                let end = *end;
+                events.next();
+                self.out.write_str(text)?;
+                self.out.write_str("</")?;
+                self.out.write_str(end)?;
+                return self.out.write_char('>');
+            }
+            self.depth += 1;
+            self.line()?;
+            return self.out.write_str(text);
+        }
+        if let Some(XmlEvent::PopElement { name: Some(end) }) = events.peek() {
+            let end = *end;
+            events.next();
+            self.out.write_str("</")?;
+            self.out.write_str(end)?;
+            return self.out.write_char('>');
+        }
+
+        // declarations are never closed
+        if !declaration {
+            self.depth += 1;
+        }
+        Ok(())
+    }
+
+    /// Starts a new line at the current depth
+    fn line(&mut self) -> fmt::Result {
+        if !self.first_line {
+            self.out.write_char('\n')?;
+        }
+        self.first_line = false;
+        self.indent(self.depth)
+    }
+
+    fn indent(&mut self, depth: usize) -> fmt::Result {
+        for _ in 0..depth * self.options.indent {
+            self.out.write_char(' ')?;
+        }
+        Ok(())
+    }
+}
+
+fn write_comment(out: &mut impl Write, text: &str) -> fmt::Result {
+    out.write_str("<!--")?;
+    out.write_str(text)?;
+    out.write_str("-->")
+}
+
+/// Width of an attribute or comment inside a start tag
+fn item_width(item: &XmlEvent) -> usize {
+    match *item {
+        XmlEvent::Attr { name, value } => {
+            name.chars().count() + value.map_or(0, |value| value.chars().count() + 3)
+        }
+        XmlEvent::Comment { text } => text.chars().count() + 7,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILES: [&str; 4] = [
+        include_str!("../benches/files/fonts.conf"),
+        include_str!("../benches/files/huge.xml"),
+        include_str!("../benches/files/large.plist"),
+        include_str!("../benches/files/medium.svg"),
+    ];
+
+    #[test]
+    fn reindents() {
+        let src = "<?xml version=\"1.0\"?><!-- c -->\n<r>\n<a   x=\"1\"\n  y/>\n\t<b> text \n more</b><c></c><!-- end --></r>";
+        assert_eq!(
+            format_to_string(src, &FormatOptions::default()),
+            "<?xml version=\"1.0\"?>\n<!-- c -->\n<r>\n  <a x=\"1\" y/>\n  <b>\n    text\n    more\n  </b>\n  <c></c>\n  <!-- end -->\n</r>\n"
+        );
+    }
+
+    #[test]
+    fn wraps_attributes() {
+        let options = FormatOptions {
+            indent: 2,
+            max_width: Some(20),
+        };
+        assert_eq!(
+            format_to_string("<r><path id=\"a\" d=\"M 0 0\"/><a x=\"1\"/></r>", &options),
+            "<r>\n  <path\n    id=\"a\"\n    d=\"M 0 0\"/>\n  <a x=\"1\"/>\n</r>\n"
+        );
+    }
+
+    #[test]
+    fn comments() {
+        let options = FormatOptions::default();
+        assert_eq!(
+            format_to_string("<r <!-- 1 --> a <!-- 2 -->><!-- 3 --></r>", &options),
+            "<r <!-- 1 --> a>\n  <!-- 2 -->\n  <!-- 3 -->\n</r>\n"
+        );
+        assert_eq!(
+            format_to_string("<r <!-- 1 -->/>", &options),
+            "<r <!-- 1 -->/>\n"
+        );
+    }
+
+    #[test]
+    fn idempotent_and_lossless() {
+        for options in [
+            FormatOptions::default(),
+            FormatOptions {
+                indent: 3,
+                max_width: Some(40),
+            },
+        ] {
+            for src in FILES {
+                let formatted = format_to_string(src, &options);
+                assert_eq!(format_to_string(&formatted, &options), formatted);
+                assert!(XmlIter::from(formatted.as_str())
+                    .with_comments(true)
+                    .eq(XmlIter::from(src).with_comments(true)));
+            }
+        }
+    }
+}
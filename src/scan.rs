@@ -0,0 +1,330 @@
+//! Byte searches used to jump straight to the next interesting delimiter.
+//!
+//! With the `memchr` feature the searches are done by the [`memchr`](https://docs.rs/memchr) crate,
+//! which also picks AVX2 (when `std` is enabled for runtime detection) or NEON. Otherwise
+//! SSE2 is used on `x86_64` and a portable word at a time search everywhere else.
+
+#[cfg(not(feature = "memchr"))]
+use core::mem::size_of;
+
+/// Index of the first `a` or `b` in the `haystack`
+#[inline(always)]
+pub fn memchr2(a: u8, b: u8, haystack: &[u8]) -> Option<usize> {
+    #[cfg(feature = "memchr")]
+    {
+        memchr::memchr2(a, b, haystack)
+    }
+    #[cfg(all(
+        not(feature = "memchr"),
+        target_arch = "x86_64",
+        target_feature = "sse2"
+    ))]
+    {
+        sse2::memchr2(a, b, haystack)
+    }
+    #[cfg(all(
+        not(feature = "memchr"),
+        not(all(target_arch = "x86_64", target_feature = "sse2"))
+    ))]
+    {
+        swar::memchr2(a, b, haystack)
+    }
+}
+
+/// Index of the first `a` in the `haystack`
+#[inline(always)]
+pub fn memchr(a: u8, haystack: &[u8]) -> Option<usize> {
+    #[cfg(feature = "memchr")]
+    {
+        memchr::memchr(a, haystack)
+    }
+    #[cfg(not(feature = "memchr"))]
+    {
+        memchr2(a, a, haystack)
+    }
+}
+
+/// Index of the first occurrence of `needle` in the `haystack`
+#[inline(always)]
+pub fn find(needle: &[u8], haystack: &[u8]) -> Option<usize> {
+    #[cfg(feature = "memchr")]
+    {
+        memchr::memmem::find(haystack, needle)
+    }
+    #[cfg(not(feature = "memchr"))]
+    {
+        let (&first, rest) = needle.split_first()?;
+        let mut offset = 0;
+        while let Some(i) = memchr2(first, first, &haystack[offset..]) {
+            let i = offset + i;
+            if haystack[i + 1..].starts_with(rest) {
+                return Some(i);
+            }
+            offset = i + 1;
+        }
+        None
+    }
+}
+
+/// Offset past the closing `"` of a quoted string, `pos` is right after the opening `"`
+#[inline(always)]
+fn string_end(bytes: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        pos += memchr2(b'\"', b'\\', &bytes[pos..])?;
+        if bytes[pos] == b'\"' {
+            return Some(pos + 1);
+        }
+        // skip the escaped byte, continuation bytes never match the delimiters
+        pos += 2;
+        if pos > bytes.len() {
+            return None;
+        }
+    }
+}
+
+/// Offset past the closing `-->` of a comment, `pos` is right after the opening `<!--`
+#[inline(always)]
+pub fn comment_end(bytes: &[u8], pos: usize) -> Option<usize> {
+    find(b"-->", &bytes[pos..]).map(|i| pos + i + 3)
+}
+
+/// Given `pos` somewhere inside a start tag (after the `<`), returns the offset past its `>`
+/// and whether the tag was self closing, skips quoted attribute values and comments
+pub fn tag_end(bytes: &[u8], mut pos: usize) -> Option<(usize, bool)> {
+    while let Some(&byte) = bytes.get(pos) {
+        match byte {
+            b'"' => pos = string_end(bytes, pos + 1)?,
+            b'<' if bytes[pos + 1..].starts_with(b"!--") => pos = comment_end(bytes, pos + 4)?,
+            b'>' => return Some((pos + 1, pos > 0 && bytes[pos - 1] == b'/')),
+            _ => pos += 1,
+        }
+    }
+    None
+}
+
+/// Byte offsets of an element inside its source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bounds {
+    /// Past the `>` of the start tag
+    pub content: usize,
+    /// At the `<` of the end tag, same as `content` for self closing elements
+    pub content_end: usize,
+    /// Past the `>` of the end tag
+    pub end: usize,
+}
+
+/// Given `pos` somewhere inside a start tag (after the `<`), finds the matching end tag,
+/// only looks at `<` while tracking the depth
+///
+/// Returns `Err` with the bounds up to the end of the input when the element isn't closed
+pub fn element_bounds(bytes: &[u8], pos: usize) -> Result<Bounds, Bounds> {
+    let eof = Bounds {
+        content: bytes.len(),
+        content_end: bytes.len(),
+        end: bytes.len(),
+    };
+
+    let Some((content, self_closing)) = tag_end(bytes, pos) else {
+        return Err(eof);
+    };
+    if self_closing {
+        return Ok(Bounds {
+            content,
+            content_end: content,
+            end: content,
+        });
+    }
+
+    let eof = Bounds { content, ..eof };
+    let mut pos = content;
+    let mut depth = 1usize;
+    loop {
+        let Some(i) = memchr(b'<', &bytes[pos..]) else {
+            return Err(eof);
+        };
+        pos += i;
+        let rest = &bytes[pos + 1..];
+        if rest.starts_with(b"!--") {
+            pos = comment_end(bytes, pos + 4).ok_or(eof)?;
+        } else if rest.starts_with(b"/") {
+            let content_end = pos;
+            pos += memchr(b'>', rest).ok_or(eof)? + 2;
+            depth -= 1;
+            if depth == 0 {
+                return Ok(Bounds {
+                    content,
+                    content_end,
+                    end: pos,
+                });
+            }
+        } else {
+            let (end, self_closing) = tag_end(bytes, pos + 1).ok_or(eof)?;
+            pos = end;
+            if !self_closing {
+                depth += 1;
+            }
+        }
+    }
+}
+
+#[cfg(all(
+    not(feature = "memchr"),
+    target_arch = "x86_64",
+    target_feature = "sse2"
+))]
+mod sse2 {
+    use core::arch::x86_64::*;
+
+    const LANES: usize = 16;
+
+    #[inline(always)]
+    pub fn memchr2(a: u8, b: u8, haystack: &[u8]) -> Option<usize> {
+        let len = haystack.len();
+        let ptr = haystack.as_ptr();
+        let mut i = 0;
+        // SAFETY: sse2 is enabled for this target and every load is inside `haystack`
+        unsafe {
+            let va = _mm_set1_epi8(a as i8);
+            let vb = _mm_set1_epi8(b as i8);
+            while i + LANES <= len {
+                let chunk = _mm_loadu_si128(ptr.add(i) as *const __m128i);
+                let eq = _mm_or_si128(_mm_cmpeq_epi8(chunk, va), _mm_cmpeq_epi8(chunk, vb));
+                let mask = _mm_movemask_epi8(eq);
+                if mask != 0 {
+                    return Some(i + mask.trailing_zeros() as usize);
+                }
+                i += LANES;
+            }
+        }
+        super::naive::memchr2(a, b, &haystack[i..]).map(|j| i + j)
+    }
+}
+
+#[cfg(not(feature = "memchr"))]
+#[allow(unused)]
+mod swar {
+    use super::*;
+
+    const WORD: usize = size_of::<usize>();
+    const LO: usize = usize::from_ne_bytes([0x01; WORD]);
+    const HI: usize = usize::from_ne_bytes([0x80; WORD]);
+
+    /// Broadcasts `byte` into every byte of a word
+    #[inline(always)]
+    const fn splat(byte: u8) -> usize {
+        LO * byte as usize
+    }
+
+    /// True if any byte of `x` is zero
+    #[inline(always)]
+    const fn has_zero(x: usize) -> bool {
+        x.wrapping_sub(LO) & !x & HI != 0
+    }
+
+    #[inline(always)]
+    pub fn memchr2(a: u8, b: u8, haystack: &[u8]) -> Option<usize> {
+        let (va, vb) = (splat(a), splat(b));
+        let mut chunks = haystack.chunks_exact(WORD);
+        let mut i = 0;
+        for chunk in &mut chunks {
+            let word = usize::from_ne_bytes(chunk.try_into().unwrap());
+            if has_zero(word ^ va) || has_zero(word ^ vb) {
+                break;
+            }
+            i += WORD;
+        }
+        super::naive::memchr2(a, b, &haystack[i..]).map(|j| i + j)
+    }
+}
+
+#[cfg(not(feature = "memchr"))]
+mod naive {
+    #[inline(always)]
+    pub fn memchr2(a: u8, b: u8, haystack: &[u8]) -> Option<usize> {
+        haystack.iter().position(|&x| x == a || x == b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn naive_find(needle: &[u8], haystack: &[u8]) -> Option<usize> {
+        haystack.windows(needle.len()).position(|w| w == needle)
+    }
+
+    #[test]
+    fn memchr2_matches_naive() {
+        let mut haystack = [b'a'; 67];
+        for len in 0..haystack.len() {
+            for at in 0..len {
+                haystack[at] = b'<';
+                for offset in 0..len.min(3) {
+                    let slice = &haystack[offset..len];
+                    let expected = slice.iter().position(|&x| x == b'\n' || x == b'<');
+                    assert_eq!(memchr2(b'\n', b'<', slice), expected);
+                    #[cfg(not(feature = "memchr"))]
+                    assert_eq!(swar::memchr2(b'\n', b'<', slice), expected);
+                }
+                haystack[at] = b'a';
+            }
+            assert_eq!(memchr2(b'\n', b'<', &haystack[..len]), None);
+        }
+    }
+
+    #[test]
+    fn element_ends() {
+        fn end(src: &str) -> Option<&str> {
+            element_bounds(src.as_bytes(), 1)
+                .ok()
+                .map(|b| &src[..b.end])
+        }
+        assert_eq!(end("<a/> tail"), Some("<a/>"));
+        assert_eq!(end("<a></a> tail"), Some("<a></a>"));
+        assert_eq!(end("<a><a><b/></a></a > tail"), Some("<a><a><b/></a></a >"));
+        assert_eq!(
+            end(r#"<a v="/>\"</a>"></a> tail"#),
+            Some(r#"<a v="/>\"</a>"></a>"#)
+        );
+        assert_eq!(
+            end("<a <!-- /> --> ><!-- </a> --></a> tail"),
+            Some("<a <!-- /> --> ><!-- </a> --></a>")
+        );
+        assert_eq!(end("<a><b>"), None);
+        assert_eq!(end("<a v=\"\\"), None);
+
+        let src = "<a x><b></b>text</a>";
+        let bounds = element_bounds(src.as_bytes(), 1);
+        assert_eq!(
+            bounds,
+            Ok(Bounds {
+                content: 5,
+                content_end: 16,
+                end: 20
+            })
+        );
+        assert_eq!(
+            element_bounds(b"<a><b>", 1),
+            Err(Bounds {
+                content: 3,
+                content_end: 6,
+                end: 6
+            })
+        );
+    }
+
+    #[test]
+    fn find_matches_naive() {
+        let inputs: [&[u8]; 6] = [
+            b"",
+            b"-->",
+            b"- -- --->",
+            b"comment with no end --",
+            b"a long comment, that has more bytes than a single simd lane -- - -->",
+            "サイボーグ-->".as_bytes(),
+        ];
+        for input in inputs {
+            assert_eq!(find(b"-->", input), naive_find(b"-->", input));
+        }
+    }
+}
@@ -0,0 +1,1280 @@
+//! Serializes Rust types as xml1 markup with [`serde`], enabled with the `serde` feature.
+//!
+//! Uses the same conventions as the [`de`](crate::de) module, so the output deserializes
+//! back into the same value:
+//! - the value is the root element, named after the struct, or see [`Serializer::with_root`]
+//! - fields with the `@` prefix are attributes, see [`Serializer::with_attr_prefix`],
+//!   `true` is written as a valueless attribute like `<r clip>`
+//! - the `$text` field is the text of the element
+//! - any other field is a child element, sequences are repeated elements
+//! - `None` is omitted, unit variants are texts and other variants are a child element
+//!
+//! Attributes are always written before child elements, whatever the order of the fields,
+//! structs and maps are serialized twice, first only for their attributes, so their child
+//! elements are written right away without buffering them.
+//! Attribute values are escaped with backslashes, so they come back as they are from the parser,
+//! texts are written raw and are an [`Error`] when they wouldn't be read back the same.
+//!
+//! ```rust
+//! # use serde::Serialize;
+//! #[derive(Serialize)]
+//! struct Font<'a> {
+//!     size: Vec<f32>,
+//!     #[serde(rename = "@family")]
+//!     family: &'a str,
+//!     #[serde(rename = "@hinting")]
+//!     hinting: bool,
+//!     #[serde(rename = "@style")]
+//!     style: Option<&'a str>,
+//! }
+//!
+//! let font = Font { size: vec![12.0, 14.5], family: "\"Fira\"", hinting: true, style: None };
+//! assert_eq!(
+//!     xml1::ser::to_string(&font).unwrap(),
+//!     r#"<Font family="\"Fira\"" hinting><size>12</size><size>14.5</size></Font>"#
+//! );
+//! ```
+
+use alloc::{
+    borrow::Cow,
+    string::{String, ToString},
+};
+use core::fmt::{self, Write};
+
+use serde::ser::{self, Impossible, Serialize};
+
+use crate::{de::TEXT_KEY, XmlEvent, XmlWriter};
+
+/// Error of [`to_string`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    pub message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl ser::StdError for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self {
+            message: msg.to_string(),
+        }
+    }
+}
+
+impl From<fmt::Error> for Error {
+    fn from(_: fmt::Error) -> Self {
+        Self {
+            message: "formatter error".into(),
+        }
+    }
+}
+
+fn unsupported(what: &str) -> Error {
+    ser::Error::custom(format_args!("{} are not supported", what))
+}
+
+/// Serializes `value` as a document
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String, Error> {
+    let mut ser = Serializer::new(String::new());
+    ser.serialize(value)?;
+    ser.into_inner()
+}
+
+/// Writes Rust types as markup, see the [module docs](self)
+pub struct Serializer<W> {
+    writer: XmlWriter<W>,
+    attr_prefix: &'static str,
+    root: Option<&'static str>,
+}
+
+impl<W: Write> Serializer<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            writer: XmlWriter::new(out),
+            attr_prefix: "@",
+            root: None,
+        }
+    }
+
+    /// Prefix of the field names of attributes, `@` by default, when empty every field
+    /// is a child element
+    pub fn with_attr_prefix(mut self, prefix: &'static str) -> Self {
+        self.attr_prefix = prefix;
+        self
+    }
+
+    /// Name of the root element, needed for values without a name like maps or numbers
+    pub fn with_root(mut self, name: &'static str) -> Self {
+        self.root = Some(name);
+        self
+    }
+
+    /// Writes `value` as the root element
+    pub fn serialize<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        value.serialize(Root { ser: self, value })
+    }
+
+    pub fn into_inner(self) -> Result<W, Error> {
+        Ok(self.writer.finish()?)
+    }
+}
+
+/// Checks that `name` can be read back as the name of an element or an attribute
+fn check_name(name: &str) -> Result<&str, Error> {
+    let invalid = |ch: char| ch.is_whitespace() || matches!(ch, '<' | '>' | '/' | '=' | '"');
+    if name.is_empty() || name.contains(invalid) || name.starts_with(['?', '!']) {
+        return Err(ser::Error::custom(format_args!("invalid name `{}`", name)));
+    }
+    Ok(name)
+}
+
+/// Checks that `text` is read back as it is, texts are raw so it can't have a `'<'`,
+/// and the parser trims every line and skips empty ones
+fn check_text(text: &str) -> Result<&str, Error> {
+    if text.contains('<')
+        || text
+            .split('\n')
+            .any(|line| line.is_empty() || line.trim() != line)
+    {
+        return Err(ser::Error::custom(format_args!("invalid text `{}`", text)));
+    }
+    Ok(text)
+}
+
+/// Raw xml1 attribute value of `s`, escapes backslashes and `'"'`, new lines and tabs are
+/// written as `\uXXXX`
+fn escape(s: &str) -> Cow<'_, str> {
+    let needs_escape = |ch: char| matches!(ch, '\\' | '"' | '\n' | '\t' | '\r');
+    if !s.contains(needs_escape) {
+        return Cow::Borrowed(s);
+    }
+
+    let mut out = String::with_capacity(s.len() + 8);
+    for ch in s.chars() {
+        match ch {
+            '\\' | '"' => {
+                out.push('\\');
+                out.push(ch);
+            }
+            '\n' | '\t' | '\r' => {
+                let _ = write!(out, "\\u{:04X}", ch as u32);
+            }
+            _ => out.push(ch),
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// Name of the attribute of a field, `None` for texts and child elements
+fn attr_name<'k>(key: &'k str, prefix: &str) -> Option<&'k str> {
+    if key == TEXT_KEY || prefix.is_empty() {
+        return None;
+    }
+    key.strip_prefix(prefix)
+}
+
+/// Writes the root element of [`Serializer::serialize`]
+struct Root<'a, 'v, W, T: ?Sized> {
+    ser: &'a mut Serializer<W>,
+    value: &'v T,
+}
+
+impl<'a, 'v, W: Write, T: ?Sized> Root<'a, 'v, W, T> {
+    fn element(
+        self,
+        name: Option<&'static str>,
+    ) -> Result<ElementSerializer<'a, 'static, 'v, W, T>, Error> {
+        let Some(name) = self.ser.root.or(name) else {
+            return Err(ser::Error::custom(
+                "the root element needs a name, see `Serializer::with_root`",
+            ));
+        };
+        Ok(ElementSerializer {
+            ser: self.ser,
+            name: check_name(name)?,
+            field: false,
+            value: self.value,
+        })
+    }
+}
+
+macro_rules! forward_to_element {
+    ($($method:ident $ty:ty)*) => {$(
+        fn $method(self, v: $ty) -> Result<(), Error> {
+            ser::Serializer::$method(self.element(None)?, v)
+        }
+    )*};
+}
+
+impl<'a, W: Write, T: Serialize + ?Sized> ser::Serializer for Root<'a, '_, W, T> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = SeqSerializer<'a, 'static, W>;
+    type SerializeTuple = SeqSerializer<'a, 'static, W>;
+    type SerializeTupleStruct = SeqSerializer<'a, 'static, W>;
+    type SerializeTupleVariant = SeqSerializer<'a, 'static, W>;
+    type SerializeMap = StructSerializer<'a, 'static, W>;
+    type SerializeStruct = StructSerializer<'a, 'static, W>;
+    type SerializeStructVariant = StructSerializer<'a, 'static, W>;
+
+    forward_to_element! {
+        serialize_bool bool serialize_i8 i8 serialize_i16 i16 serialize_i32 i32
+        serialize_i64 i64 serialize_u8 u8 serialize_u16 u16 serialize_u32 u32
+        serialize_u64 u64 serialize_f32 f32 serialize_f64 f64 serialize_char char
+        serialize_str &str serialize_bytes &[u8]
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_some<U: Serialize + ?Sized>(self, value: &U) -> Result<(), Error> {
+        value.serialize(Root {
+            ser: self.ser,
+            value,
+        })
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        ser::Serializer::serialize_unit(self.element(None)?)
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<(), Error> {
+        ser::Serializer::serialize_unit(self.element(Some(name))?)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        ser::Serializer::serialize_unit_variant(self.element(Some(name))?, name, index, variant)
+    }
+
+    fn serialize_newtype_struct<U: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &U,
+    ) -> Result<(), Error> {
+        let element = self.element(Some(name))?;
+        value.serialize(ElementSerializer {
+            ser: element.ser,
+            name: element.name,
+            field: false,
+            value,
+        })
+    }
+
+    fn serialize_newtype_variant<U: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        value: &U,
+    ) -> Result<(), Error> {
+        ser::Serializer::serialize_newtype_variant(
+            self.element(Some(name))?,
+            name,
+            index,
+            variant,
+            value,
+        )
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        ser::Serializer::serialize_seq(self.element(None)?, len)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Error> {
+        ser::Serializer::serialize_tuple(self.element(None)?, len)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        ser::Serializer::serialize_tuple_struct(self.element(Some(name))?, name, len)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        ser::Serializer::serialize_tuple_variant(
+            self.element(Some(name))?,
+            name,
+            index,
+            variant,
+            len,
+        )
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        ser::Serializer::serialize_map(self.element(None)?, len)
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        ser::Serializer::serialize_struct(self.element(Some(name))?, name, len)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        ser::Serializer::serialize_struct_variant(
+            self.element(Some(name))?,
+            name,
+            index,
+            variant,
+            len,
+        )
+    }
+}
+
+/// Writes `value` as elements called `name`
+struct ElementSerializer<'a, 'n, 'v, W, T: ?Sized> {
+    ser: &'a mut Serializer<W>,
+    name: &'n str,
+    /// Field of a parent element, sequences are written as repeated elements
+    field: bool,
+    /// Serialized again for the attributes of structs and maps
+    value: &'v T,
+}
+
+impl<W: Write, T: Serialize + ?Sized> ElementSerializer<'_, '_, '_, W, T> {
+    /// `<name>text</name>`
+    fn text(self, value: impl Serialize) -> Result<(), Error> {
+        let Some(text) = value.serialize(ValueSerializer { attr: false })? else {
+            return Ok(());
+        };
+        let writer = &mut self.ser.writer;
+        writer.write(XmlEvent::PushElement { name: self.name })?;
+        writer.write(XmlEvent::StartTagEnd {
+            self_closing: false,
+        })?;
+        let text = text.unwrap_or_default();
+        if !text.is_empty() {
+            writer.write(XmlEvent::Text {
+                text: check_text(&text)?,
+            })?;
+        }
+        writer.write(XmlEvent::PopElement {
+            name: Some(self.name),
+        })?;
+        Ok(())
+    }
+
+    /// Writes the attributes of the struct or map being serialized, right after its name
+    fn attrs(&mut self) -> Result<(), Error> {
+        self.value.serialize(AttrsSerializer {
+            writer: &mut self.ser.writer,
+            prefix: self.ser.attr_prefix,
+            key: None,
+        })
+    }
+}
+
+macro_rules! serialize_text {
+    ($($method:ident $ty:ty)*) => {$(
+        fn $method(self, v: $ty) -> Result<(), Error> {
+            self.text(v)
+        }
+    )*};
+}
+
+impl<'a, 'n, W: Write, T: Serialize + ?Sized> ser::Serializer
+    for ElementSerializer<'a, 'n, '_, W, T>
+{
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = SeqSerializer<'a, 'n, W>;
+    type SerializeTuple = SeqSerializer<'a, 'n, W>;
+    type SerializeTupleStruct = SeqSerializer<'a, 'n, W>;
+    type SerializeTupleVariant = SeqSerializer<'a, 'n, W>;
+    type SerializeMap = StructSerializer<'a, 'n, W>;
+    type SerializeStruct = StructSerializer<'a, 'n, W>;
+    type SerializeStructVariant = StructSerializer<'a, 'n, W>;
+
+    serialize_text! {
+        serialize_bool bool serialize_i8 i8 serialize_i16 i16 serialize_i32 i32
+        serialize_i64 i64 serialize_u8 u8 serialize_u16 u16 serialize_u32 u32
+        serialize_u64 u64 serialize_f32 f32 serialize_f64 f64 serialize_char char
+        serialize_str &str
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+        self.text(String::from_utf8_lossy(v).as_ref())
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_some<U: Serialize + ?Sized>(self, value: &U) -> Result<(), Error> {
+        value.serialize(ElementSerializer {
+            ser: self.ser,
+            name: self.name,
+            field: self.field,
+            value,
+        })
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        self.ser
+            .writer
+            .write(XmlEvent::PushElement { name: self.name })?;
+        self.ser.writer.write(XmlEvent::PopElement { name: None })?;
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.text(variant)
+    }
+
+    fn serialize_newtype_struct<U: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &U,
+    ) -> Result<(), Error> {
+        self.serialize_some(value)
+    }
+
+    fn serialize_newtype_variant<U: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &U,
+    ) -> Result<(), Error> {
+        let writer = &mut self.ser.writer;
+        writer.write(XmlEvent::PushElement { name: self.name })?;
+        writer.write(XmlEvent::StartTagEnd {
+            self_closing: false,
+        })?;
+        value.serialize(ElementSerializer {
+            ser: &mut *self.ser,
+            name: variant,
+            field: false,
+            value,
+        })?;
+        self.ser.writer.write(XmlEvent::PopElement {
+            name: Some(self.name),
+        })?;
+        Ok(())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        if !self.field {
+            self.ser
+                .writer
+                .write(XmlEvent::PushElement { name: self.name })?;
+        }
+        Ok(SeqSerializer {
+            ser: self.ser,
+            name: self.name,
+            field: self.field,
+            empty: true,
+            outer: None,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        let writer = &mut self.ser.writer;
+        writer.write(XmlEvent::PushElement { name: self.name })?;
+        writer.write(XmlEvent::PushElement { name: variant })?;
+        Ok(SeqSerializer {
+            ser: self.ser,
+            name: variant,
+            field: false,
+            empty: true,
+            outer: Some(self.name),
+        })
+    }
+
+    fn serialize_map(mut self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        self.ser
+            .writer
+            .write(XmlEvent::PushElement { name: self.name })?;
+        self.attrs()?;
+        Ok(StructSerializer {
+            ser: self.ser,
+            name: self.name,
+            outer: None,
+            key: String::new(),
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        mut self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        let writer = &mut self.ser.writer;
+        writer.write(XmlEvent::PushElement { name: self.name })?;
+        writer.write(XmlEvent::PushElement { name: variant })?;
+        self.attrs()?;
+        Ok(StructSerializer {
+            ser: self.ser,
+            name: variant,
+            outer: Some(self.name),
+            key: String::new(),
+        })
+    }
+}
+
+/// Items of a sequence, either as repeated elements or as `item` children
+struct SeqSerializer<'a, 'n, W> {
+    ser: &'a mut Serializer<W>,
+    name: &'n str,
+    field: bool,
+    empty: bool,
+    /// Element wrapping this one, for enum variants
+    outer: Option<&'n str>,
+}
+
+impl<W: Write> SeqSerializer<'_, '_, W> {
+    fn item<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.empty = false;
+        value.serialize(ElementSerializer {
+            ser: &mut *self.ser,
+            name: if self.field { self.name } else { "item" },
+            field: false,
+            value,
+        })
+    }
+
+    fn finish(self) -> Result<(), Error> {
+        let writer = &mut self.ser.writer;
+        if !self.field {
+            writer.write(XmlEvent::PopElement {
+                name: (!self.empty).then_some(self.name),
+            })?;
+        }
+        if let Some(name) = self.outer {
+            writer.write(XmlEvent::PopElement { name: Some(name) })?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> ser::SerializeSeq for SeqSerializer<'_, '_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.item(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<W: Write> ser::SerializeTuple for SeqSerializer<'_, '_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.item(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<W: Write> ser::SerializeTupleStruct for SeqSerializer<'_, '_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.item(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<W: Write> ser::SerializeTupleVariant for SeqSerializer<'_, '_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.item(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+/// Fields of an element, after its attributes, the content is written as it comes
+struct StructSerializer<'a, 'n, W> {
+    ser: &'a mut Serializer<W>,
+    name: &'n str,
+    /// Element wrapping this one, for enum variants
+    outer: Option<&'n str>,
+    /// Last map key
+    key: String,
+}
+
+impl<W: Write> StructSerializer<'_, '_, W> {
+    fn field<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<(), Error> {
+        if key == TEXT_KEY {
+            if let Some(text) = value.serialize(ValueSerializer { attr: false })? {
+                let text = text.unwrap_or_default();
+                if !text.is_empty() {
+                    self.ser.writer.write(XmlEvent::Text {
+                        text: check_text(&text)?,
+                    })?;
+                }
+            }
+        } else if attr_name(key, self.ser.attr_prefix).is_none() {
+            value.serialize(ElementSerializer {
+                ser: &mut *self.ser,
+                name: check_name(key)?,
+                field: true,
+                value,
+            })?;
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), Error> {
+        let writer = &mut self.ser.writer;
+        // self closing when nothing was written after the attributes
+        let name = (!writer.is_start_tag_open()).then_some(self.name);
+        writer.write(XmlEvent::PopElement { name })?;
+        if let Some(name) = self.outer {
+            writer.write(XmlEvent::PopElement { name: Some(name) })?;
+        }
+        Ok(())
+    }
+}
+
+/// String of a map key
+fn map_key<T: Serialize + ?Sized>(key: &T) -> Result<String, Error> {
+    match key.serialize(ValueSerializer { attr: false })? {
+        Some(Some(key)) => Ok(key),
+        _ => Err(ser::Error::custom("map keys must be strings")),
+    }
+}
+
+impl<W: Write> ser::SerializeMap for StructSerializer<'_, '_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.key = map_key(key)?;
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = core::mem::take(&mut self.key);
+        self.field(&key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<W: Write> ser::SerializeStruct for StructSerializer<'_, '_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<W: Write> ser::SerializeStructVariant for StructSerializer<'_, '_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+/// Writes only the attributes of a struct or a map, the first time it's serialized
+struct AttrsSerializer<'a, W> {
+    writer: &'a mut XmlWriter<W>,
+    prefix: &'static str,
+    /// Last map key
+    key: Option<String>,
+}
+
+impl<W: Write> AttrsSerializer<'_, W> {
+    fn field<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<(), Error> {
+        let Some(name) = attr_name(key, self.prefix) else {
+            return Ok(());
+        };
+        let name = check_name(name)?;
+        if let Some(value) = value.serialize(ValueSerializer { attr: true })? {
+            self.writer.write(XmlEvent::Attr {
+                name,
+                value: value.as_deref().map(escape).as_deref(),
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// The value was serialized as something else the first time
+fn changed() -> Error {
+    ser::Error::custom("the value changed while being serialized")
+}
+
+macro_rules! serialize_changed {
+    ($($method:ident $ty:ty)*) => {$(
+        fn $method(self, _: $ty) -> Result<(), Error> {
+            Err(changed())
+        }
+    )*};
+}
+
+impl<W: Write> ser::Serializer for AttrsSerializer<'_, W> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Impossible<(), Error>;
+    type SerializeTuple = Impossible<(), Error>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    serialize_changed! {
+        serialize_bool bool serialize_i8 i8 serialize_i16 i16 serialize_i32 i32
+        serialize_i64 i64 serialize_u8 u8 serialize_u16 u16 serialize_u32 u32
+        serialize_u64 u64 serialize_f32 f32 serialize_f64 f64 serialize_char char
+        serialize_str &str serialize_bytes &[u8]
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        Err(changed())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, _value: &T) -> Result<(), Error> {
+        Err(changed())
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        Err(changed())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        Err(changed())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+    ) -> Result<(), Error> {
+        Err(changed())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _value: &T,
+    ) -> Result<(), Error> {
+        Err(changed())
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), Error> {
+        Err(changed())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(changed())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(changed())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(changed())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(changed())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Ok(self)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Ok(self)
+    }
+}
+
+impl<W: Write> ser::SerializeMap for AttrsSerializer<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.key = Some(map_key(key)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self.key.take().unwrap_or_default();
+        self.field(&key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<W: Write> ser::SerializeStruct for AttrsSerializer<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<W: Write> ser::SerializeStructVariant for AttrsSerializer<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Formats attribute values and texts, unescaped
+///
+/// `None` for values to omit, `Some(None)` for valueless attributes
+struct ValueSerializer {
+    attr: bool,
+}
+
+type Value = Option<Option<String>>;
+
+macro_rules! serialize_display {
+    ($($method:ident $ty:ty)*) => {$(
+        fn $method(self, v: $ty) -> Result<Value, Error> {
+            Ok(Some(Some(v.to_string())))
+        }
+    )*};
+}
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = Error;
+    type SerializeSeq = Impossible<Value, Error>;
+    type SerializeTuple = Impossible<Value, Error>;
+    type SerializeTupleStruct = Impossible<Value, Error>;
+    type SerializeTupleVariant = Impossible<Value, Error>;
+    type SerializeMap = Impossible<Value, Error>;
+    type SerializeStruct = Impossible<Value, Error>;
+    type SerializeStructVariant = Impossible<Value, Error>;
+
+    serialize_display! {
+        serialize_i8 i8 serialize_i16 i16 serialize_i32 i32 serialize_i64 i64
+        serialize_u8 u8 serialize_u16 u16 serialize_u32 u32 serialize_u64 u64
+        serialize_f32 f32 serialize_f64 f64 serialize_char char serialize_str &str
+    }
+
+    fn serialize_bool(self, v: bool) -> Result<Value, Error> {
+        if self.attr && v {
+            Ok(Some(None))
+        } else {
+            Ok(Some(Some(v.to_string())))
+        }
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, Error> {
+        match core::str::from_utf8(v) {
+            Ok(v) => self.serialize_str(v),
+            Err(_) => Err(unsupported("non UTF-8 bytes")),
+        }
+    }
+
+    fn serialize_none(self) -> Result<Value, Error> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value, Error> {
+        Ok(Some(Some(String::new())))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Value, Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Value, Error> {
+        Err(unsupported(
+            "enum variants with values in attributes or texts",
+        ))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(unsupported("sequences in attributes or texts"))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(unsupported("tuples in attributes or texts"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(unsupported("tuples in attributes or texts"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(unsupported(
+            "enum variants with values in attributes or texts",
+        ))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(unsupported("maps in attributes or texts"))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        Err(unsupported("structs in attributes or texts"))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(unsupported(
+            "enum variants with values in attributes or texts",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, string::String, vec, vec::Vec};
+
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::{de, XmlIter};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(rename = "config")]
+    struct Config {
+        #[serde(default)]
+        dir: Vec<Dir>,
+        #[serde(rename = "@version")]
+        version: u32,
+        #[serde(rename = "@debug", default)]
+        debug: bool,
+        #[serde(rename = "@name")]
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cache: Option<String>,
+        mode: Mode,
+        shape: Shape,
+        #[serde(rename = "$text", default)]
+        note: String,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Dir {
+        #[serde(rename = "@prefix")]
+        prefix: Option<String>,
+        #[serde(rename = "$text")]
+        path: String,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Mode {
+        Fast,
+        Slow,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Shape {
+        Circle {
+            #[serde(rename = "@r")]
+            r: f32,
+        },
+        Point,
+        Line(f32, f32),
+        Tag(String),
+    }
+
+    fn round_trip<T>(value: &T) -> String
+    where
+        T: Serialize + for<'de> Deserialize<'de> + PartialEq + fmt::Debug,
+    {
+        let out = to_string(value).unwrap();
+        // valid xml1
+        XmlIter::from(out.as_str()).for_each(drop);
+        assert_eq!(&de::from_str::<T>(&out).unwrap(), value, "{}", out);
+        out
+    }
+
+    #[test]
+    fn structs() {
+        let mut config = Config {
+            dir: vec![
+                Dir {
+                    prefix: None,
+                    path: "/usr/share/fonts".into(),
+                },
+                Dir {
+                    prefix: Some("say \"hi\" \\o/".into()),
+                    path: "a > b \\ c\nd".into(),
+                },
+            ],
+            version: 2,
+            debug: true,
+            name: "x".into(),
+            cache: None,
+            mode: Mode::Slow,
+            shape: Shape::Circle { r: 1.5 },
+            note: "note".into(),
+        };
+        assert_eq!(
+            round_trip(&config),
+            concat!(
+                r#"<config version="2" debug name="x">"#,
+                r#"<dir>/usr/share/fonts</dir>"#,
+                "<dir prefix=\"say \\\"hi\\\" \\\\o/\">a > b \\ c\nd</dir>",
+                r#"<mode>slow</mode><shape><circle r="1.5"/></shape>note</config>"#
+            )
+        );
+
+        config.debug = false;
+        config.cache = Some(String::new());
+        config.dir.clear();
+        config.note.clear();
+        for shape in [Shape::Point, Shape::Line(1.0, -2.0), Shape::Tag("t".into())] {
+            config.shape = shape;
+            round_trip(&config);
+        }
+        assert_eq!(
+            round_trip(&config),
+            concat!(
+                r#"<config version="2" debug="false" name="x">"#,
+                r#"<cache></cache><mode>slow</mode><shape><tag>t</tag></shape></config>"#
+            )
+        );
+    }
+
+    #[test]
+    fn roots() {
+        let mut ser = Serializer::new(String::new()).with_root("m");
+        let map = BTreeMap::from([("@x", "1"), ("y", "2"), ("$text", "t")]);
+        ser.serialize(&map).unwrap();
+        assert_eq!(ser.into_inner().unwrap(), r#"<m x="1">t<y>2</y></m>"#);
+
+        let mut ser = Serializer::new(String::new())
+            .with_root("list")
+            .with_attr_prefix("");
+        ser.serialize(&vec![1, 2]).unwrap();
+        assert_eq!(
+            ser.into_inner().unwrap(),
+            "<list><item>1</item><item>2</item></list>"
+        );
+
+        assert_eq!(
+            to_string(&5).unwrap_err().message,
+            "the root element needs a name, see `Serializer::with_root`"
+        );
+        let list: Vec<u8> = de::from_str("<list><item>1</item><item>2</item></list>").unwrap();
+        assert_eq!(list, [1, 2]);
+
+        for (key, name) in [
+            ("", ""),
+            ("a b", "a b"),
+            ("@", ""),
+            ("@a=b", "a=b"),
+            ("a>b", "a>b"),
+            ("a<b", "a<b"),
+            ("a/b", "a/b"),
+            ("a\"b", "a\"b"),
+            ("!a", "!a"),
+        ] {
+            let mut ser = Serializer::new(String::new()).with_root("m");
+            let err = ser.serialize(&BTreeMap::from([(key, "1")])).unwrap_err();
+            assert_eq!(err.message, std::format!("invalid name `{}`", name));
+        }
+        let mut ser = Serializer::new(String::new()).with_root("a b");
+        assert!(ser.serialize(&5).is_err());
+
+        for text in ["1 < 2", " a", "a\t", "a\n\nb", "a \nb"] {
+            let mut ser = Serializer::new(String::new()).with_root("t");
+            let err = ser.serialize(text).unwrap_err();
+            assert_eq!(err.message, std::format!("invalid text `{}`", text));
+        }
+    }
+}
@@ -0,0 +1,307 @@
+//! Compact representation of all events of a document for repeated traversals.
+//!
+//! The document is parsed once into a flat array of `u32` words, each event is a kind
+//! followed by offsets and lengths into the source, instead of `&str` fat pointers.
+//! Every [`XmlEvent::PushElement`] also stores where its element ends on the tape,
+//! so whole subtrees can be skipped in O(1).
+//!
+//! | event                       | words                                                  |
+//! |-----------------------------|--------------------------------------------------------|
+//! | `PushElement`               | `PUSH, name offset, name len, end`                     |
+//! | `PopElement { name: Some }` | `POP, name offset, name len`                           |
+//! | `PopElement { name: None }` | `POP_SELF`                                             |
+//! | `Attr { value: Some }`      | `ATTR, name offset, name len, value offset, value len` |
+//! | `Attr { value: None }`      | `ATTR_FLAG, name offset, name len`                     |
+//! | `Text`                      | `TEXT, offset, len`                                    |
+//! | `Comment`                   | `COMMENT, offset, len`                                 |
+//! | `StartTagEnd`               | `START_TAG_END, self_closing`                          |
+//!
+//! ```rust
+//! # use xml1::{tape::Tape, XmlEvent, XmlIter};
+//! let src = "<r><metadata><a/></metadata><b/></r>";
+//! let tape = Tape::parse(src);
+//! assert!(tape.iter().eq(XmlIter::from(src)));
+//!
+//! let mut iter = tape.iter();
+//! iter.next();
+//! assert_eq!(iter.next(), Some(XmlEvent::PushElement { name: "metadata" }));
+//! iter.skip_element();
+//! assert_eq!(iter.next(), Some(XmlEvent::PushElement { name: "b" }));
+//! ```
+
+use alloc::vec::Vec;
+
+use crate::{XmlEvent, XmlIter};
+
+pub const PUSH: u32 = 0;
+pub const POP: u32 = 1;
+pub const POP_SELF: u32 = 2;
+pub const ATTR: u32 = 3;
+pub const ATTR_FLAG: u32 = 4;
+pub const TEXT: u32 = 5;
+pub const COMMENT: u32 = 6;
+pub const START_TAG_END: u32 = 7;
+
+/// Position in a tape of `len` words, panics if it doesn't fit in a word
+#[inline(always)]
+fn tape_index(len: usize) -> u32 {
+    assert!(len <= u32::MAX as usize, "too many events");
+    len as u32
+}
+
+/// Flat array of events, see the [module docs](self)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tape<'a> {
+    src: &'a str,
+    words: Vec<u32>,
+}
+
+impl<'a> Tape<'a> {
+    /// Parses the whole `src`, which must be under 4 GiB and its tape must fit in `u32` words
+    pub fn parse(src: &'a str) -> Self {
+        Self::parse_iter(XmlIter::from(src))
+    }
+
+    /// Stores all the events of `iter`, use it to keep comments or start tag ends
+    pub fn parse_iter(iter: XmlIter<'a>) -> Self {
+        let src = iter.input.source();
+        assert!(src.len() <= u32::MAX as usize, "source is too large");
+
+        let mut words = Vec::new();
+        // positions in the tape of the `end` word of open elements
+        let mut stack = Vec::new();
+        let offset = |s: &str| (s.as_ptr() as usize - src.as_ptr() as usize) as u32;
+
+        for evn in iter {
+            match evn {
+                XmlEvent::PushElement { name } => {
+                    words.extend_from_slice(&[PUSH, offset(name), name.len() as u32, 0]);
+                    stack.push(words.len() - 1);
+                }
+                XmlEvent::PopElement { name } => {
+                    match name {
+                        Some(name) => {
+                            words.extend_from_slice(&[POP, offset(name), name.len() as u32])
+                        }
+                        None => words.push(POP_SELF),
+                    }
+                    if let Some(end) = stack.pop() {
+                        words[end] = tape_index(words.len());
+                    }
+                }
+                XmlEvent::Attr { name, value } => match value {
+                    Some(value) => words.extend_from_slice(&[
+                        ATTR,
+                        offset(name),
+                        name.len() as u32,
+                        offset(value),
+                        value.len() as u32,
+                    ]),
+                    None => words.extend_from_slice(&[ATTR_FLAG, offset(name), name.len() as u32]),
+                },
+                XmlEvent::Text { text } => {
+                    words.extend_from_slice(&[TEXT, offset(text), text.len() as u32])
+                }
+                XmlEvent::Comment { text } => {
+                    words.extend_from_slice(&[COMMENT, offset(text), text.len() as u32])
+                }
+                XmlEvent::StartTagEnd { self_closing } => {
+                    words.extend_from_slice(&[START_TAG_END, self_closing as u32])
+                }
+            }
+        }
+
+        // unclosed elements end with the tape
+        let len = tape_index(words.len());
+        for end in stack {
+            words[end] = len;
+        }
+
+        Self { src, words }
+    }
+
+    #[inline(always)]
+    pub fn source(&self) -> &'a str {
+        self.src
+    }
+
+    /// Raw encoded tape
+    #[inline(always)]
+    pub fn words(&self) -> &[u32] {
+        &self.words
+    }
+
+    #[inline(always)]
+    pub fn iter(&self) -> TapeIter<'_, 'a> {
+        TapeIter {
+            src: self.src,
+            words: &self.words,
+            pos: 0,
+            element_end: None,
+        }
+    }
+}
+
+impl<'t, 'a> IntoIterator for &'t Tape<'a> {
+    type Item = XmlEvent<'a>;
+    type IntoIter = TapeIter<'t, 'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterates over the [`XmlEvent`]s stored in a [`Tape`]
+#[derive(Clone)]
+pub struct TapeIter<'t, 'a> {
+    src: &'a str,
+    words: &'t [u32],
+    pos: usize,
+    /// End of the last pushed element, while still in its start tag
+    element_end: Option<usize>,
+}
+
+impl<'t, 'a> TapeIter<'t, 'a> {
+    /// Position of the next event in the tape words
+    #[inline(always)]
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Jumps over the element just pushed and all of its children in O(1),
+    /// the matching [`XmlEvent::PopElement`] is skipped as well.
+    ///
+    /// Must be called right after a [`XmlEvent::PushElement`], before any event other than
+    /// [`XmlEvent::Attr`]
+    pub fn skip_element(&mut self) {
+        self.pos = self
+            .element_end
+            .take()
+            .expect("`skip_element` must be called right after a `PushElement`");
+    }
+
+    #[inline(always)]
+    fn str(&self, at: usize) -> &'a str {
+        let offset = self.words[at] as usize;
+        let len = self.words[at + 1] as usize;
+        &self.src[offset..offset + len]
+    }
+}
+
+impl<'t, 'a> Iterator for TapeIter<'t, 'a> {
+    type Item = XmlEvent<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let pos = self.pos;
+        let kind = *self.words.get(pos)?;
+        let mut element_end = None;
+        let (evn, len) = match kind {
+            PUSH => {
+                element_end = Some(self.words[pos + 3] as usize);
+                (
+                    XmlEvent::PushElement {
+                        name: self.str(pos + 1),
+                    },
+                    4,
+                )
+            }
+            POP => (
+                XmlEvent::PopElement {
+                    name: Some(self.str(pos + 1)),
+                },
+                3,
+            ),
+            POP_SELF => (XmlEvent::PopElement { name: None }, 1),
+            ATTR => (
+                XmlEvent::Attr {
+                    name: self.str(pos + 1),
+                    value: Some(self.str(pos + 3)),
+                },
+                5,
+            ),
+            ATTR_FLAG => (
+                XmlEvent::Attr {
+                    name: self.str(pos + 1),
+                    value: None,
+                },
+                3,
+            ),
+            TEXT => (
+                XmlEvent::Text {
+                    text: self.str(pos + 1),
+                },
+                3,
+            ),
+            COMMENT => (
+                XmlEvent::Comment {
+                    text: self.str(pos + 1),
+                },
+                3,
+            ),
+            START_TAG_END => (
+                XmlEvent::StartTagEnd {
+                    self_closing: self.words[pos + 1] != 0,
+                },
+                2,
+            ),
+            _ => panic!("invalid tape kind `{}` at {}", kind, pos),
+        };
+        // attributes and comments keep the start tag open
+        if !matches!(evn, XmlEvent::Attr { .. } | XmlEvent::Comment { .. }) || element_end.is_some()
+        {
+            self.element_end = element_end;
+        }
+        self.pos += len;
+        Some(evn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_events() {
+        for src in [
+            include_str!("../benches/files/fonts.conf"),
+            include_str!("../benches/files/huge.xml"),
+            include_str!("../benches/files/large.plist"),
+            include_str!("../benches/files/medium.svg"),
+        ] {
+            let tape = Tape::parse(src);
+            assert!(tape.iter().eq(XmlIter::from(src)));
+            let tape = Tape::parse_iter(XmlIter::from(src).with_comments(true));
+            assert!(tape.iter().eq(XmlIter::from(src).with_comments(true)));
+            let tape = Tape::parse_iter(XmlIter::from(src).with_start_tag_end(true));
+            assert!(tape.iter().eq(XmlIter::from(src).with_start_tag_end(true)));
+        }
+    }
+
+    #[test]
+    fn skip_like_xml_iter() {
+        let src = include_str!("../benches/files/medium.svg");
+        let tape = Tape::parse(src);
+        let mut a = tape.iter();
+        let mut b = XmlIter::from(src);
+        let mut skipped = 0;
+        while let Some(evn) = a.next() {
+            assert_eq!(Some(evn), b.next());
+            if let XmlEvent::PushElement { name } = evn {
+                if name != "?xml" && skipped % 3 == 0 {
+                    a.skip_element();
+                    b.skip_element();
+                }
+                skipped += 1;
+            }
+        }
+        assert_eq!(b.next(), None);
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    #[should_panic(expected = "too many events")]
+    fn tape_too_long() {
+        assert_eq!(tape_index(u32::MAX as usize), u32::MAX);
+        tape_index(u32::MAX as usize + 1);
+    }
+}
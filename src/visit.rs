@@ -0,0 +1,358 @@
+use crate::{XmlEvent, XmlIter};
+
+/// What a [`XmlVisitor`] wants the driver to do next
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ControlFlow {
+    #[default]
+    Continue,
+    /// Skips the rest of the element, its remaining attributes and all of its children,
+    /// [`XmlVisitor::exit_element`] is still called. Same as `Continue` when returned from
+    /// `text`, `comment` or `exit_element`
+    SkipChildren,
+    /// Stops visiting right away
+    Stop,
+}
+
+/// Callbacks called by [`visit`] in document order, every method defaults to `Continue`
+///
+/// Declarations like `<?xml ...?>` are exited right after their start tag.
+pub trait XmlVisitor<'a> {
+    fn enter_element(&mut self, _name: &'a str) -> ControlFlow {
+        ControlFlow::Continue
+    }
+
+    fn attribute(&mut self, _name: &'a str, _value: Option<&'a str>) -> ControlFlow {
+        ControlFlow::Continue
+    }
+
+    /// Called once all the attributes were visited
+    fn start_tag_end(&mut self, _name: &'a str, _self_closing: bool) -> ControlFlow {
+        ControlFlow::Continue
+    }
+
+    fn text(&mut self, _text: &'a str) -> ControlFlow {
+        ControlFlow::Continue
+    }
+
+    /// Only called when visiting an [`XmlIter`] with comments, see [`visit_iter`]
+    fn comment(&mut self, _text: &'a str) -> ControlFlow {
+        ControlFlow::Continue
+    }
+
+    /// Called for every entered element, self closing ones included, `name` comes from the
+    /// end tag when there is one
+    fn exit_element(&mut self, _name: &'a str) -> ControlFlow {
+        ControlFlow::Continue
+    }
+}
+
+/// Visits the whole `src`, returns `Stop` if the visitor stopped
+///
+/// Elements are visited in a single loop that only counts the depth, so nothing is allocated
+/// and any nesting works. Skipped elements are fast-forwarded with [`XmlIter::skip_element`].
+/// Elements still open at the end of the input aren't exited.
+///
+/// ```rust
+/// # use xml1::{visit, ControlFlow, XmlVisitor};
+/// struct Widgets(usize);
+///
+/// impl<'a> XmlVisitor<'a> for Widgets {
+///     fn enter_element(&mut self, name: &'a str) -> ControlFlow {
+///         if name == "metadata" {
+///             return ControlFlow::SkipChildren;
+///         }
+///         self.0 += 1;
+///         ControlFlow::Continue
+///     }
+/// }
+///
+/// let mut widgets = Widgets(0);
+/// visit("<ui><metadata><a/></metadata><button/></ui>", &mut widgets);
+/// assert_eq!(widgets.0, 2);
+/// ```
+pub fn visit<'a>(src: &'a str, visitor: &mut impl XmlVisitor<'a>) -> ControlFlow {
+    visit_iter(XmlIter::from(src), visitor)
+}
+
+/// Same as [`visit`] for a configured `iter`, like one with comments
+pub fn visit_iter<'a>(iter: XmlIter<'a>, visitor: &mut impl XmlVisitor<'a>) -> ControlFlow {
+    // to know when to call `start_tag_end` without reading the content
+    let mut iter = iter.with_start_tag_end(true);
+    match content(&mut iter, visitor) {
+        Ok(()) => ControlFlow::Continue,
+        Err(Stopped) => ControlFlow::Stop,
+    }
+}
+
+struct Stopped;
+
+/// `true` to skip
+#[inline(always)]
+fn flow(flow: ControlFlow) -> Result<bool, Stopped> {
+    match flow {
+        ControlFlow::Continue => Ok(false),
+        ControlFlow::SkipChildren => Ok(true),
+        ControlFlow::Stop => Err(Stopped),
+    }
+}
+
+/// Visits all the events, only the depth is tracked since the end tags have the names
+fn content<'a>(iter: &mut XmlIter<'a>, visitor: &mut impl XmlVisitor<'a>) -> Result<(), Stopped> {
+    let mut depth = 0usize;
+    while let Some(evn) = iter.next() {
+        match evn {
+            XmlEvent::PushElement { name } => {
+                if start_tag(iter, visitor, name)? {
+                    depth += 1;
+                }
+            }
+            XmlEvent::PopElement { name } => {
+                // stray end tags are ignored
+                if depth == 0 {
+                    continue;
+                }
+                depth -= 1;
+                // self closing elements are exited with their start tag
+                flow(visitor.exit_element(name.unwrap_or_default()))?;
+            }
+            XmlEvent::Text { text } => {
+                flow(visitor.text(text))?;
+            }
+            XmlEvent::Comment { text } => {
+                flow(visitor.comment(text))?;
+            }
+            XmlEvent::Attr { .. } | XmlEvent::StartTagEnd { .. } => {
+                unreachable!("visited with their element")
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Visits the start tag of an element right after its `PushElement`, returns `true` when
+/// its content has to be visited, otherwise the element was already exited
+fn start_tag<'a>(
+    iter: &mut XmlIter<'a>,
+    visitor: &mut impl XmlVisitor<'a>,
+    name: &'a str,
+) -> Result<bool, Stopped> {
+    let declaration = name.starts_with('?') || name.starts_with('!');
+    let skip = |iter: &mut XmlIter<'a>| {
+        if declaration {
+            // there is no end tag to skip to
+            let end = iter.find(|evn| matches!(evn, XmlEvent::StartTagEnd { .. }));
+            if end == Some(XmlEvent::StartTagEnd { self_closing: true }) {
+                iter.next();
+            }
+        } else {
+            iter.skip_element();
+        }
+    };
+
+    if flow(visitor.enter_element(name))? {
+        skip(iter);
+        flow(visitor.exit_element(name))?;
+        return Ok(false);
+    }
+
+    let self_closing = loop {
+        match iter.next() {
+            Some(XmlEvent::Attr { name: attr, value }) => {
+                if flow(visitor.attribute(attr, value))? {
+                    skip(iter);
+                    flow(visitor.exit_element(name))?;
+                    return Ok(false);
+                }
+            }
+            // comments inside the start tag
+            Some(XmlEvent::Comment { text }) => {
+                flow(visitor.comment(text))?;
+            }
+            Some(XmlEvent::StartTagEnd { self_closing }) => break self_closing,
+            // truncated start tag, the element is left open like any other at the end
+            None => return Ok(false),
+            _ => unreachable!("start tags always end"),
+        }
+    };
+    if self_closing {
+        // `PopElement { name: None }`
+        iter.next();
+    }
+
+    let skip = flow(visitor.start_tag_end(name, self_closing))?;
+    if !self_closing && !declaration {
+        if !skip {
+            return Ok(true);
+        }
+        while let Some(evn) = iter.next() {
+            match evn {
+                XmlEvent::PushElement { .. } => {
+                    iter.skip_element();
+                }
+                XmlEvent::PopElement { .. } => break,
+                _ => {}
+            }
+        }
+    }
+    flow(visitor.exit_element(name))?;
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{string::String, vec::Vec};
+
+    use core::sync::atomic::Ordering;
+
+    use super::*;
+    use crate::tests::ALLOCATIONS_COUNT;
+
+    /// Records every callback
+    #[derive(Default)]
+    struct Log {
+        log: String,
+        stop_at: Option<&'static str>,
+        skip: Option<&'static str>,
+    }
+
+    impl<'a> XmlVisitor<'a> for Log {
+        fn enter_element(&mut self, name: &'a str) -> ControlFlow {
+            self.log += &std::format!("<{}", name);
+            if self.stop_at == Some(name) {
+                return ControlFlow::Stop;
+            }
+            if self.skip == Some(name) {
+                return ControlFlow::SkipChildren;
+            }
+            ControlFlow::Continue
+        }
+
+        fn attribute(&mut self, name: &'a str, _: Option<&'a str>) -> ControlFlow {
+            self.log += &std::format!(" {}", name);
+            if self.skip == Some(name) {
+                return ControlFlow::SkipChildren;
+            }
+            ControlFlow::Continue
+        }
+
+        fn start_tag_end(&mut self, _: &'a str, self_closing: bool) -> ControlFlow {
+            self.log += if self_closing { "/>" } else { ">" };
+            ControlFlow::Continue
+        }
+
+        fn text(&mut self, text: &'a str) -> ControlFlow {
+            self.log += text;
+            ControlFlow::Continue
+        }
+
+        fn comment(&mut self, text: &'a str) -> ControlFlow {
+            self.log += &std::format!("<!--{}-->", text);
+            ControlFlow::Continue
+        }
+
+        fn exit_element(&mut self, name: &'a str) -> ControlFlow {
+            self.log += &std::format!("</{}>", name);
+            ControlFlow::Continue
+        }
+    }
+
+    fn log(src: &str, stop_at: Option<&'static str>, skip: Option<&'static str>) -> String {
+        let mut log = Log {
+            stop_at,
+            skip,
+            ..Log::default()
+        };
+        visit_iter(XmlIter::from(src).with_comments(true), &mut log);
+        log.log
+    }
+
+    #[test]
+    fn callbacks() {
+        let src = "<?xml version=\"1.0\"?><r a=\"1\" b><x/>text<!--c--><y><z/></y></r>";
+        assert_eq!(
+            log(src, None, None),
+            "<?xml version ?></?xml><r a b><x/></x>text<!--c--><y><z/></z></y></r>"
+        );
+        assert_eq!(
+            log(src, None, Some("y")),
+            "<?xml version ?></?xml><r a b><x/></x>text<!--c--><y</y></r>"
+        );
+        assert_eq!(log(src, None, Some("a")), "<?xml version ?></?xml><r a</r>");
+        assert_eq!(
+            log(src, None, Some("version")),
+            "<?xml version</?xml><r a b><x/></x>text<!--c--><y><z/></z></y></r>"
+        );
+        assert_eq!(
+            log(src, None, Some("?xml")),
+            "<?xml</?xml><r a b><x/></x>text<!--c--><y><z/></z></y></r>"
+        );
+        assert_eq!(
+            log(src, Some("z"), None),
+            "<?xml version ?></?xml><r a b><x/></x>text<!--c--><y><z"
+        );
+        assert_eq!(log("</a><b/>", None, None), "<b/></b>");
+        assert_eq!(log("<r><a>", None, None), "<r><a>");
+        assert_eq!(log("<r><a ", None, None), "<r><a");
+        assert_eq!(log("<a x=\"1\"", None, None), "<a x");
+    }
+
+    /// Skips the children of an element after its start tag
+    struct SkipAfterStartTag<'a> {
+        names: Vec<&'a str>,
+    }
+
+    impl<'a> XmlVisitor<'a> for SkipAfterStartTag<'a> {
+        fn enter_element(&mut self, name: &'a str) -> ControlFlow {
+            self.names.push(name);
+            ControlFlow::Continue
+        }
+
+        fn start_tag_end(&mut self, name: &'a str, _: bool) -> ControlFlow {
+            if name == "defs" {
+                ControlFlow::SkipChildren
+            } else {
+                ControlFlow::Continue
+            }
+        }
+
+        fn exit_element(&mut self, name: &'a str) -> ControlFlow {
+            assert_eq!(self.names.pop(), Some(name));
+            ControlFlow::Continue
+        }
+    }
+
+    #[test]
+    fn balanced() {
+        let src = include_str!("../benches/files/medium.svg");
+        let mut visitor = SkipAfterStartTag { names: Vec::new() };
+        assert_eq!(visit(src, &mut visitor), ControlFlow::Continue);
+        assert!(visitor.names.is_empty());
+    }
+
+    #[test]
+    fn deeply_nested() {
+        let depth = 200_000;
+        let src = "<a>".repeat(depth) + &"</a>".repeat(depth);
+        let mut visitor = SkipAfterStartTag { names: Vec::new() };
+        assert_eq!(visit(&src, &mut visitor), ControlFlow::Continue);
+        assert!(visitor.names.is_empty());
+    }
+
+    #[test]
+    fn no_allocations() {
+        struct Count(usize);
+        impl XmlVisitor<'_> for Count {
+            fn exit_element(&mut self, _: &str) -> ControlFlow {
+                self.0 += 1;
+                ControlFlow::Continue
+            }
+        }
+
+        let src = include_str!("../benches/files/huge.xml");
+        ALLOCATIONS_COUNT.store(0, Ordering::Relaxed);
+        let mut count = Count(0);
+        visit(src, &mut count);
+        assert!(count.0 > 0);
+        assert_eq!(ALLOCATIONS_COUNT.load(Ordering::Relaxed), 0, "allocated");
+    }
+}
@@ -0,0 +1,405 @@
+use core::fmt::{self, Write};
+
+use crate::XmlEvent;
+
+/// Writes [`XmlEvent`]s back as markup, the counterpart of [`XmlIter`](crate::XmlIter)
+///
+/// Event strings are taken as raw xml1 strings, exactly as returned by the parser, so
+/// escape sequences such as `\"` are kept as they are. Any `'"'` in attribute values that
+/// isn't already escaped gets a backslash, so the output always parses back into the same events.
+///
+/// The output is as compact as possible, consecutive texts are separated by a new line
+/// since the parser splits texts at new lines.
+///
+/// ```rust
+/// # use xml1::{XmlEvent, XmlWriter};
+/// let mut writer = XmlWriter::new(String::new());
+/// writer.write(XmlEvent::PushElement { name: "a" })?;
+/// writer.write(XmlEvent::Attr { name: "min", value: Some("0, 0") })?;
+/// writer.write(XmlEvent::Attr { name: "clip", value: None })?;
+/// writer.write(XmlEvent::Text { text: "1 > 0" })?;
+/// writer.write(XmlEvent::PopElement { name: Some("a") })?;
+/// assert_eq!(writer.into_inner(), r#"<a min="0, 0" clip>1 > 0</a>"#);
+/// # Ok::<(), std::fmt::Error>(())
+/// ```
+pub struct XmlWriter<W> {
+    out: W,
+    /// Last event was a `PushElement` or an `Attr`, so the start tag still needs a `'>'`
+    open_tag: bool,
+    /// Last event was a `Text`
+    text: bool,
+    /// Attributes right after a quoted value aren't separated by a space, see [`minify`](crate::minify)
+    tight: bool,
+    /// Last event was an `Attr` with a value
+    quoted: bool,
+    /// Last start tag is a declaration like `<!DOCTYPE ...>`, its attributes can be quoted literals
+    declaration: bool,
+}
+
+impl<W: Write> XmlWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            open_tag: false,
+            text: false,
+            tight: false,
+            quoted: false,
+            declaration: false,
+        }
+    }
+
+    /// Leaves out the space between a quoted value and the next attribute, like `x="1"y`
+    pub(crate) fn tight(mut self) -> Self {
+        self.tight = true;
+        self
+    }
+
+    #[inline(always)]
+    pub fn get_ref(&self) -> &W {
+        &self.out
+    }
+
+    #[inline(always)]
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.out
+    }
+
+    /// A start tag was written and may still get attributes
+    #[cfg(feature = "serde")]
+    #[inline(always)]
+    pub(crate) fn is_start_tag_open(&self) -> bool {
+        self.open_tag
+    }
+
+    /// Closes any start tag left open and returns the inner writer
+    pub fn finish(mut self) -> Result<W, fmt::Error> {
+        self.close_tag()?;
+        Ok(self.out)
+    }
+
+    /// Returns the inner writer as it is, a start tag may still be open
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    /// Writes a single event
+    ///
+    /// Panics when an [`XmlEvent::Attr`], a [`XmlEvent::StartTagEnd`] or a [`XmlEvent::PopElement`]
+    /// without name isn't right after a start tag, when a text contains a `'<'`, a comment
+    /// contains `-->` or a name can't be parsed back
+    pub fn write(&mut self, evn: XmlEvent<'_>) -> fmt::Result {
+        match evn {
+            XmlEvent::PushElement { name } => {
+                self.close_tag()?;
+                self.text = false;
+                self.open_tag = true;
+                self.quoted = false;
+                self.declaration = name.starts_with(['?', '!']);
+                self.out.write_char('<')?;
+                self.out.write_str(check_name(name, false))
+            }
+            XmlEvent::PopElement { name: Some(name) } => {
+                self.close_tag()?;
+                self.text = false;
+                self.out.write_str("</")?;
+                self.out.write_str(check_name(name, false))?;
+                self.out.write_char('>')
+            }
+            XmlEvent::PopElement { name: None } => {
+                assert!(
+                    self.open_tag,
+                    "self closing `PopElement` outside of a start tag"
+                );
+                self.open_tag = false;
+                self.out.write_str("/>")
+            }
+            XmlEvent::Attr { name, value } => {
+                assert!(self.open_tag, "`Attr` outside of a start tag");
+                if !(self.tight && self.quoted) {
+                    self.out.write_char(' ')?;
+                }
+                self.quoted = value.is_some();
+                self.out.write_str(check_name(name, self.declaration))?;
+                if let Some(value) = value {
+                    self.out.write_str("=\"")?;
+                    write_escaped(&mut self.out, value, b'"')?;
+                    self.out.write_char('"')?;
+                }
+                Ok(())
+            }
+            XmlEvent::Text { text } => {
+                self.close_tag()?;
+                if self.text {
+                    self.out.write_char('\n')?;
+                }
+                self.text = true;
+                assert!(!text.contains('<'), "`Text` with a less than sign");
+                self.out.write_str(text)
+            }
+            XmlEvent::Comment { text } => {
+                assert!(!text.contains("-->"), "`Comment` with a `-->`");
+                // comments are allowed inside start tags
+                if self.open_tag {
+                    self.out.write_char(' ')?;
+                }
+                self.text = false;
+                self.quoted = false;
+                self.out.write_str("<!--")?;
+                self.out.write_str(text)?;
+                self.out.write_str("-->")
+            }
+            // the `/>` is written by the `PopElement` that follows
+            XmlEvent::StartTagEnd { self_closing: true } => {
+                assert!(self.open_tag, "`StartTagEnd` outside of a start tag");
+                Ok(())
+            }
+            XmlEvent::StartTagEnd {
+                self_closing: false,
+            } => {
+                assert!(self.open_tag, "`StartTagEnd` outside of a start tag");
+                self.close_tag()
+            }
+        }
+    }
+
+    /// Writes all `events`
+    pub fn write_all<'a>(&mut self, events: impl IntoIterator<Item = XmlEvent<'a>>) -> fmt::Result {
+        for evn in events {
+            self.write(evn)?;
+        }
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn close_tag(&mut self) -> fmt::Result {
+        if self.open_tag {
+            self.open_tag = false;
+            self.out.write_char('>')?;
+        }
+        Ok(())
+    }
+}
+
+/// Panics if `name` would be parsed back as something else, `literal` allows the quotes of
+/// declarations like `<!DOCTYPE r SYSTEM "r.dtd">`
+#[inline(always)]
+fn check_name(name: &str, literal: bool) -> &str {
+    let invalid = |ch: char| {
+        ch.is_whitespace() || matches!(ch, '<' | '>' | '/' | '=') || (ch == '"' && !literal)
+    };
+    assert!(
+        !name.is_empty() && !name.contains(invalid),
+        "invalid name `{}`",
+        name
+    );
+    name
+}
+
+/// Writes `s` adding a backslash before any `delimiter` that isn't escaped yet
+pub(crate) fn write_escaped(out: &mut impl Write, s: &str, delimiter: u8) -> fmt::Result {
+    let bytes = s.as_bytes();
+    let mut start = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let byte = bytes[i];
+        if byte == b'\\' {
+            if i + 1 == bytes.len() {
+                // a trailing backslash would escape whatever comes next
+                out.write_str(&s[start..])?;
+                return out.write_char('\\');
+            }
+            // keep escape sequences as they are, continuation bytes never match `delimiter`
+            i += 2;
+        } else if byte == delimiter {
+            out.write_str(&s[start..i])?;
+            out.write_char('\\')?;
+            start = i;
+            i += 1;
+        } else {
+            i += 1;
+        }
+    }
+    out.write_str(&s[start..])
+}
+
+/// Adapts a [`std::io::Write`] to be used by the [`XmlWriter`]
+#[cfg(feature = "std")]
+pub struct IoWriter<W> {
+    inner: W,
+    error: Option<std::io::Error>,
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> IoWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self { inner, error: None }
+    }
+
+    /// Takes the io error behind the last [`fmt::Error`]
+    pub fn take_error(&mut self) -> Option<std::io::Error> {
+        self.error.take()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> Write for IoWriter<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.inner.write_all(s.as_bytes()).map_err(|err| {
+            self.error = Some(err);
+            fmt::Error
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> XmlWriter<IoWriter<W>> {
+    /// Writes into a [`std::io::Write`]
+    pub fn from_io(inner: W) -> Self {
+        Self::new(IoWriter::new(inner))
+    }
+
+    /// Same as [`XmlWriter::write`] but returns the underlying io error
+    pub fn write_io(&mut self, evn: XmlEvent<'_>) -> std::io::Result<()> {
+        self.write(evn).map_err(|_| {
+            self.out
+                .take_error()
+                .unwrap_or_else(|| std::io::Error::other("formatter error"))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::string::String;
+
+    use super::*;
+    use crate::XmlIter;
+
+    fn write(src: &str) -> String {
+        let mut writer = XmlWriter::new(String::new());
+        writer.write_all(XmlIter::from(src)).unwrap();
+        writer.finish().unwrap()
+    }
+
+    fn round_trip(src: &str) {
+        let out = write(src);
+        assert!(
+            XmlIter::from(out.as_str()).eq(XmlIter::from(src)),
+            "{:?} != {:?}",
+            src,
+            out
+        );
+    }
+
+    #[test]
+    fn compact() {
+        assert_eq!(
+            write("<r>\n  <a  clip  min = \"0\" />\n</r>"),
+            "<r><a clip min=\"0\"/></r>"
+        );
+        assert_eq!(
+            write("<r> some \n text <!-- x --> </r>"),
+            "<r>some\ntext</r>"
+        );
+        assert_eq!(write("<r <!-- x --> >"), "<r>");
+    }
+
+    #[test]
+    fn escapes() {
+        let mut writer = XmlWriter::new(String::new());
+        writer.write(XmlEvent::PushElement { name: "r" }).unwrap();
+        writer
+            .write(XmlEvent::Attr {
+                name: "v",
+                value: Some(r#"say "hi" \"there\" \"#),
+            })
+            .unwrap();
+        writer.write(XmlEvent::Text { text: r"a>b \ c" }).unwrap();
+        writer
+            .write(XmlEvent::PopElement { name: Some("r") })
+            .unwrap();
+        let out = writer.finish().unwrap();
+        assert_eq!(out, r#"<r v="say \"hi\" \"there\" \\">a>b \ c</r>"#);
+        round_trip(&out);
+    }
+
+    #[test]
+    fn start_tag_end() {
+        let src = include_str!("../benches/files/medium.svg");
+        let mut writer = XmlWriter::new(String::new());
+        writer
+            .write_all(XmlIter::from(src).with_start_tag_end(true))
+            .unwrap();
+        assert_eq!(writer.finish().unwrap(), write(src));
+    }
+
+    #[test]
+    fn round_trips() {
+        round_trip("<r></r>");
+        round_trip("<r><a><b></b></a></r>");
+        round_trip("<r toggle color=\"#fff\"/>");
+        round_trip(r#"<r text="\"escaped\" sequence \u0041"></r>"#);
+        round_trip("<a>  some <!-- not --> text  </a>");
+        round_trip("  text\n only  ");
+        round_trip(r#"<サイボーグ 難易度="難しい" ></サイボーグ>"#);
+        round_trip(include_str!("../benches/files/fonts.conf"));
+        round_trip(include_str!("../benches/files/huge.xml"));
+        round_trip(include_str!("../benches/files/large.plist"));
+        round_trip(include_str!("../benches/files/medium.svg"));
+    }
+
+    #[test]
+    fn comments() {
+        let src = "<!--a--><r <!-- b --> x>text<!-- c -->text</r>";
+        let mut writer = XmlWriter::new(String::new());
+        writer
+            .write_all(XmlIter::from(src).with_comments(true))
+            .unwrap();
+        let out = writer.finish().unwrap();
+        assert_eq!(out, "<!--a--><r <!-- b --> x>text<!-- c -->text</r>");
+        assert!(XmlIter::from(out.as_str())
+            .with_comments(true)
+            .eq(XmlIter::from(src).with_comments(true)));
+    }
+
+    #[test]
+    #[should_panic(expected = "`Comment` with a `-->`")]
+    fn comment_end() {
+        let mut writer = XmlWriter::new(String::new());
+        let _ = writer.write(XmlEvent::Comment { text: "a --> b" });
+    }
+
+    #[test]
+    fn names() {
+        for name in ["a b", "a>b", "a<b", "a/b", "a=b", "a\"b", ""] {
+            let push = std::panic::catch_unwind(|| {
+                XmlWriter::new(String::new()).write(XmlEvent::PushElement { name })
+            });
+            assert!(push.is_err(), "{:?}", name);
+            let attr = std::panic::catch_unwind(|| {
+                let mut writer = XmlWriter::new(String::new());
+                writer.write(XmlEvent::PushElement { name: "r" }).unwrap();
+                writer.write(XmlEvent::Attr { name, value: None })
+            });
+            assert!(attr.is_err(), "{:?}", name);
+        }
+        round_trip("<!DOCTYPE r SYSTEM \"r.dtd\"><r/>");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn io() {
+        let mut writer = XmlWriter::from_io(std::vec::Vec::new());
+        writer
+            .write_io(XmlEvent::PushElement { name: "a" })
+            .unwrap();
+        writer
+            .write_io(XmlEvent::PopElement { name: None })
+            .unwrap();
+        assert_eq!(writer.into_inner().into_inner(), b"<a/>");
+    }
+}
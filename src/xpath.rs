@@ -0,0 +1,1043 @@
+//! Subset of XPath 1.0 to query a [`Document`], or an [`XmlIter`] without building a tree.
+//!
+//! Supported are the child (`/`) and descendant (`//`) axes, `.` and `..`, name tests and `*`,
+//! `@name`, `@*` and `text()` steps, and predicates with positions, `position()`, `last()`,
+//! `not()`, `and`, `or` and the comparisons `=`, `!=`, `<`, `<=`, `>` and `>=`.
+//!
+//! ```rust
+//! # use xml1::{dom::Document, xpath::Query};
+//! let src = "<r><types><type count=\"2000\" name=\"a\"/><type count=\"10\" name=\"b\"/></types></r>";
+//! let query = Query::parse("//types/type[@count > 1000]/@name").unwrap();
+//!
+//! let doc = Document::parse(src);
+//! let names: Vec<_> = query.select(&doc).iter().map(|item| item.string_value()).collect();
+//! assert_eq!(names, ["a"]);
+//! ```
+//!
+//! Queries that only look at names, attributes and positions can also run directly
+//! over the events, see [`Query::stream`].
+
+use alloc::{borrow::Cow, boxed::Box, collections::VecDeque, string::String, vec, vec::Vec};
+use core::{cmp::Ordering, fmt};
+
+use crate::{
+    dom::{Attribute, Document, NodeKind, NodeRef},
+    scan, XmlEvent, XmlIter,
+};
+
+/// Error of [`Query::parse`] and [`Query::stream`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueryError {
+    /// Byte offset in the expression
+    pub position: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.message, self.position)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Axis {
+    Child,
+    /// `//`, children of the node or any of its descendants
+    Descendant,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Test<'q> {
+    Name(&'q str),
+    /// `*`
+    Any,
+    /// `text()`
+    Text,
+    /// `@name` or `@*`
+    Attr(Option<&'q str>),
+    /// `.`
+    SelfNode,
+    /// `..`
+    Parent,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Step<'q> {
+    axis: Axis,
+    test: Test<'q>,
+    predicates: Vec<Expr<'q>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr<'q> {
+    Number(f64),
+    String(&'q str),
+    Path(Path<'q>),
+    Position,
+    Last,
+    Not(Box<Expr<'q>>),
+    And(Box<Expr<'q>>, Box<Expr<'q>>),
+    Or(Box<Expr<'q>>, Box<Expr<'q>>),
+    Cmp(Box<Expr<'q>>, Op, Box<Expr<'q>>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Path<'q> {
+    absolute: bool,
+    steps: Vec<Step<'q>>,
+}
+
+/// Parsed XPath expression, see the [module docs](self)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query<'q> {
+    path: Path<'q>,
+}
+
+impl<'q> Query<'q> {
+    pub fn parse(expr: &'q str) -> Result<Self, QueryError> {
+        let mut parser = Parser { src: expr, pos: 0 };
+        let path = parser.path()?;
+        parser.ws();
+        if parser.pos != expr.len() {
+            return Err(parser.error("unexpected character"));
+        }
+        Ok(Self { path })
+    }
+
+    /// True if the query can run with [`Query::stream`]: `.`, `..`, `last()` and
+    /// predicates looking at anything besides attributes and positions aren't supported,
+    /// and `@` and `text()` steps must be the last
+    pub fn is_streamable(&self) -> bool {
+        let steps = &self.path.steps;
+        steps.len() <= 64
+            && steps.iter().enumerate().all(|(i, step)| match step.test {
+                Test::Name(_) | Test::Any => step.predicates.iter().all(streamable),
+                Test::Text | Test::Attr(_) => i == steps.len() - 1 && step.predicates.is_empty(),
+                Test::SelfNode | Test::Parent => false,
+            })
+    }
+
+    /// Matches of the query in document order, relative queries start at the document node
+    pub fn select<'d, 'a>(&self, doc: &'d Document<'a>) -> Vec<Item<'d, 'a>> {
+        self.select_from(doc.document())
+    }
+
+    /// Matches of the query in document order, relative queries start at `node`
+    pub fn select_from<'d, 'a>(&self, node: NodeRef<'d, 'a>) -> Vec<Item<'d, 'a>> {
+        select(&self.path, node)
+    }
+
+    /// Runs the query over the events of `iter`, see [`Query::is_streamable`]
+    pub fn stream<'x, 'a>(&'x self, iter: XmlIter<'a>) -> Result<Stream<'x, 'a>, QueryError> {
+        if !self.is_streamable() {
+            return Err(QueryError {
+                position: 0,
+                message: "query can't be streamed",
+            });
+        }
+        let text = self
+            .path
+            .steps
+            .iter()
+            .enumerate()
+            .filter(|(_, step)| step.test == Test::Text)
+            .fold(0, |mask, (k, _)| mask | 1 << k);
+        Ok(Stream {
+            steps: &self.path.steps,
+            text,
+            iter,
+            frames: vec![Frame {
+                active: 1,
+                counters: Vec::new(),
+            }],
+            pending: None,
+            attrs: Vec::new(),
+            queue: VecDeque::new(),
+        })
+    }
+}
+
+fn streamable(expr: &Expr<'_>) -> bool {
+    match expr {
+        Expr::Number(_) | Expr::String(_) | Expr::Position => true,
+        Expr::Last => false,
+        Expr::Not(expr) => streamable(expr),
+        Expr::And(a, b) | Expr::Or(a, b) | Expr::Cmp(a, _, b) => streamable(a) && streamable(b),
+        Expr::Path(path) => {
+            !path.absolute
+                && matches!(&path.steps[..], [step] if step.axis == Axis::Child
+                    && matches!(step.test, Test::Attr(_))
+                    && step.predicates.is_empty())
+        }
+    }
+}
+
+/// Node or attribute matched by a [`Query`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Item<'d, 'a> {
+    Node(NodeRef<'d, 'a>),
+    Attr {
+        element: NodeRef<'d, 'a>,
+        attr: &'d Attribute<'a>,
+    },
+}
+
+impl<'d, 'a> Item<'d, 'a> {
+    pub fn node(&self) -> Option<NodeRef<'d, 'a>> {
+        match self {
+            Item::Node(node) => Some(*node),
+            Item::Attr { .. } => None,
+        }
+    }
+
+    /// Value of an attribute or the text of a node, including the text of all its descendants
+    pub fn string_value(&self) -> Cow<'a, str> {
+        match self {
+            Item::Attr { attr, .. } => Cow::Borrowed(attr.value.unwrap_or("")),
+            Item::Node(node) => match node.kind() {
+                NodeKind::Text { text } | NodeKind::Comment { text } => Cow::Borrowed(text),
+                _ => {
+                    let mut texts = node
+                        .descendants()
+                        .filter(|n| n.is_text())
+                        .filter_map(|n| n.text());
+                    let first = texts.next().unwrap_or("");
+                    match texts.next() {
+                        None => Cow::Borrowed(first),
+                        Some(second) => {
+                            let mut value = String::from(first);
+                            value.push_str(second);
+                            texts.for_each(|text| value.push_str(text));
+                            Cow::Owned(value)
+                        }
+                    }
+                }
+            },
+        }
+    }
+
+    /// Position in the document
+    fn key(&self) -> (usize, usize) {
+        match self {
+            Item::Node(node) => (node.id(), 0),
+            Item::Attr { element, attr } => {
+                let attrs = element.attrs();
+                let i = (*attr as *const Attribute<'_> as usize - attrs.as_ptr() as usize)
+                    / core::mem::size_of::<Attribute<'_>>();
+                (element.id(), i + 1)
+            }
+        }
+    }
+}
+
+fn select<'d, 'a>(path: &Path<'_>, node: NodeRef<'d, 'a>) -> Vec<Item<'d, 'a>> {
+    let start = if path.absolute {
+        node.document().document()
+    } else {
+        node
+    };
+    let mut items = vec![Item::Node(start)];
+    for step in &path.steps {
+        let mut next = Vec::new();
+        for item in items {
+            let Item::Node(node) = item else {
+                continue;
+            };
+            match step.axis {
+                Axis::Child => select_step(step, node, &mut next),
+                Axis::Descendant => {
+                    for node in node.descendants() {
+                        select_step(step, node, &mut next);
+                    }
+                }
+            }
+        }
+        next.sort_by_key(Item::key);
+        next.dedup_by_key(|item| item.key());
+        items = next;
+    }
+    items
+}
+
+/// Appends the matches of `step` from `node`
+fn select_step<'d, 'a>(step: &Step<'_>, node: NodeRef<'d, 'a>, out: &mut Vec<Item<'d, 'a>>) {
+    let mut group: Vec<Item<'d, 'a>> = match step.test {
+        Test::Name(name) => node
+            .children()
+            .filter(|n| n.name() == Some(name))
+            .map(Item::Node)
+            .collect(),
+        Test::Any => node
+            .children()
+            .filter(|n| n.is_element())
+            .map(Item::Node)
+            .collect(),
+        Test::Text => node
+            .children()
+            .filter(|n| n.is_text())
+            .map(Item::Node)
+            .collect(),
+        Test::Attr(name) => node
+            .attrs()
+            .iter()
+            .filter(|attr| name.is_none_or(|name| attr.name == name))
+            .map(|attr| Item::Attr {
+                element: node,
+                attr,
+            })
+            .collect(),
+        Test::SelfNode => vec![Item::Node(node)],
+        Test::Parent => node.parent().map(Item::Node).into_iter().collect(),
+    };
+    for predicate in &step.predicates {
+        let last = group.len();
+        let mut position = 0;
+        group.retain(|item| {
+            position += 1;
+            let context = DomContext {
+                item: *item,
+                position,
+                last,
+            };
+            predicate_truth(predicate, &context)
+        });
+    }
+    out.extend(group);
+}
+
+/// What predicates are evaluated against
+trait Context<'s> {
+    fn position(&self) -> usize;
+    fn last(&self) -> usize;
+    /// String values of the matches of `path`
+    fn path(&self, path: &Path<'_>) -> Vec<Cow<'s, str>>;
+}
+
+struct DomContext<'d, 'a> {
+    item: Item<'d, 'a>,
+    position: usize,
+    last: usize,
+}
+
+impl<'a> Context<'a> for DomContext<'_, 'a> {
+    fn position(&self) -> usize {
+        self.position
+    }
+
+    fn last(&self) -> usize {
+        self.last
+    }
+
+    fn path(&self, path: &Path<'_>) -> Vec<Cow<'a, str>> {
+        match self.item {
+            Item::Node(node) => select(path, node).iter().map(Item::string_value).collect(),
+            // attributes have no children
+            Item::Attr { .. } => Vec::new(),
+        }
+    }
+}
+
+enum Value<'s> {
+    Set(Vec<Cow<'s, str>>),
+    Number(f64),
+    String(Cow<'s, str>),
+    Bool(bool),
+}
+
+impl Value<'_> {
+    fn truth(&self) -> bool {
+        match self {
+            Value::Set(set) => !set.is_empty(),
+            Value::Number(n) => *n != 0.0 && !n.is_nan(),
+            Value::String(s) => !s.is_empty(),
+            Value::Bool(b) => *b,
+        }
+    }
+}
+
+fn number(s: &str) -> f64 {
+    s.trim().parse().unwrap_or(f64::NAN)
+}
+
+fn predicate_truth<'s>(expr: &'s Expr<'_>, context: &impl Context<'s>) -> bool {
+    match eval(expr, context) {
+        Value::Number(n) => n == context.position() as f64,
+        value => value.truth(),
+    }
+}
+
+fn eval<'s>(expr: &'s Expr<'_>, context: &impl Context<'s>) -> Value<'s> {
+    match expr {
+        Expr::Number(n) => Value::Number(*n),
+        Expr::String(s) => Value::String(Cow::Borrowed(s)),
+        Expr::Path(path) => Value::Set(context.path(path)),
+        Expr::Position => Value::Number(context.position() as f64),
+        Expr::Last => Value::Number(context.last() as f64),
+        Expr::Not(expr) => Value::Bool(!eval(expr, context).truth()),
+        Expr::And(a, b) => Value::Bool(eval(a, context).truth() && eval(b, context).truth()),
+        Expr::Or(a, b) => Value::Bool(eval(a, context).truth() || eval(b, context).truth()),
+        Expr::Cmp(a, op, b) => Value::Bool(compare(&eval(a, context), *op, &eval(b, context))),
+    }
+}
+
+fn compare(a: &Value<'_>, op: Op, b: &Value<'_>) -> bool {
+    match (a, b) {
+        (Value::Set(a), Value::Set(b)) => a.iter().any(|a| b.iter().any(|b| compare_str(a, op, b))),
+        (Value::Set(set), Value::Bool(_)) | (Value::Bool(_), Value::Set(set)) => {
+            let set = Value::Bool(!set.is_empty());
+            match a {
+                Value::Set(_) => compare(&set, op, b),
+                _ => compare(a, op, &set),
+            }
+        }
+        (Value::Set(set), other) => set
+            .iter()
+            .any(|s| compare(&Value::String(Cow::Borrowed(s)), op, other)),
+        (other, Value::Set(set)) => set
+            .iter()
+            .any(|s| compare(other, op, &Value::String(Cow::Borrowed(s)))),
+        (Value::Bool(_), _) | (_, Value::Bool(_)) => {
+            compare_ordering(a.truth().partial_cmp(&b.truth()), op)
+        }
+        (Value::Number(a), Value::Number(b)) => compare_ordering(a.partial_cmp(b), op),
+        (Value::Number(a), Value::String(b)) => compare_ordering(a.partial_cmp(&number(b)), op),
+        (Value::String(a), Value::Number(b)) => compare_ordering(number(a).partial_cmp(b), op),
+        (Value::String(a), Value::String(b)) => compare_str(a, op, b),
+    }
+}
+
+/// Strings are equal by value and ordered as numbers
+fn compare_str(a: &str, op: Op, b: &str) -> bool {
+    match op {
+        Op::Eq => a == b,
+        Op::Ne => a != b,
+        _ => compare_ordering(number(a).partial_cmp(&number(b)), op),
+    }
+}
+
+fn compare_ordering(ordering: Option<Ordering>, op: Op) -> bool {
+    match (op, ordering) {
+        // NaN is different from anything
+        (Op::Ne, None) => true,
+        (_, None) => false,
+        (Op::Eq, Some(o)) => o.is_eq(),
+        (Op::Ne, Some(o)) => o.is_ne(),
+        (Op::Lt, Some(o)) => o.is_lt(),
+        (Op::Le, Some(o)) => o.is_le(),
+        (Op::Gt, Some(o)) => o.is_gt(),
+        (Op::Ge, Some(o)) => o.is_ge(),
+    }
+}
+
+/// Match of a [`Stream`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamMatch<'a> {
+    Element {
+        name: &'a str,
+        outer_xml: &'a str,
+    },
+    Attr {
+        name: &'a str,
+        value: Option<&'a str>,
+    },
+    Text {
+        text: &'a str,
+    },
+}
+
+struct Frame {
+    /// Bit mask of the steps the children of the element are tested against
+    active: u64,
+    /// Matches of (step, predicate) among the children, for positional predicates
+    counters: Vec<((usize, usize), usize)>,
+}
+
+/// Runs a [`Query`] over the events of an [`XmlIter`], see [`Query::stream`]
+///
+/// ```rust
+/// # use xml1::{xpath::{Query, StreamMatch}, XmlIter};
+/// let src = "<r><a x=\"1\"/><a x=\"2\"><b>text</b></a></r>";
+/// let query = Query::parse("/r/a[@x = 2]/b/text()").unwrap();
+/// let matches: Vec<_> = query.stream(XmlIter::from(src)).unwrap().collect();
+/// assert_eq!(matches, [StreamMatch::Text { text: "text" }]);
+/// ```
+pub struct Stream<'x, 'a> {
+    steps: &'x [Step<'x>],
+    /// Bit mask of the `text()` steps
+    text: u64,
+    iter: XmlIter<'a>,
+    /// Open elements, starting with the document
+    frames: Vec<Frame>,
+    /// Name and start of an element whose attributes are still being read
+    pending: Option<(&'a str, usize)>,
+    attrs: Vec<(&'a str, Option<&'a str>)>,
+    queue: VecDeque<StreamMatch<'a>>,
+}
+
+impl<'a> Iterator for Stream<'_, 'a> {
+    type Item = StreamMatch<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(found) = self.queue.pop_front() {
+                return Some(found);
+            }
+
+            let evn = self.iter.next()?;
+            if let XmlEvent::StartTagEnd { .. } = evn {
+                continue;
+            }
+            if let XmlEvent::Attr { name, value } = evn {
+                self.attrs.push((name, value));
+                continue;
+            }
+            if let Some((name, start)) = self.pending.take() {
+                if !self.start_tag_end(name, start) && evn == (XmlEvent::PopElement { name: None })
+                {
+                    continue;
+                }
+            }
+
+            match evn {
+                XmlEvent::PushElement { name } => {
+                    self.pending = Some((name, self.iter.start));
+                    self.attrs.clear();
+                }
+                XmlEvent::PopElement { .. } => {
+                    // stray end tags are ignored
+                    if self.frames.len() > 1 {
+                        self.frames.pop();
+                    }
+                }
+                XmlEvent::Text { text } => {
+                    if self.frames.last().unwrap().active & self.text != 0 {
+                        self.queue.push_back(StreamMatch::Text { text });
+                    }
+                }
+                XmlEvent::Attr { .. } | XmlEvent::Comment { .. } | XmlEvent::StartTagEnd { .. } => {
+                }
+            }
+        }
+    }
+}
+
+struct StreamContext<'s, 'a> {
+    attrs: &'s [(&'a str, Option<&'a str>)],
+    position: usize,
+}
+
+impl<'a> Context<'a> for StreamContext<'_, 'a> {
+    fn position(&self) -> usize {
+        self.position
+    }
+
+    fn last(&self) -> usize {
+        unreachable!("not streamable")
+    }
+
+    fn path(&self, path: &Path<'_>) -> Vec<Cow<'a, str>> {
+        let Test::Attr(name) = path.steps[0].test else {
+            unreachable!("not streamable")
+        };
+        self.attrs
+            .iter()
+            .filter(|(attr, _)| name.is_none_or(|name| *attr == name))
+            .map(|(_, value)| Cow::Borrowed(value.unwrap_or("")))
+            .collect()
+    }
+}
+
+impl<'a> Stream<'_, 'a> {
+    /// Tests the element that just had all its attributes read, returns `false` for
+    /// declarations, that are never opened
+    fn start_tag_end(&mut self, name: &'a str, start: usize) -> bool {
+        let steps = self.steps;
+        let frame = self.frames.last_mut().unwrap();
+        let mut active = 0u64;
+        let mut element = false;
+        // `@` steps applied to this element
+        let mut attr_steps = 0u64;
+
+        let mut bits = frame.active;
+        while bits != 0 {
+            let k = bits.trailing_zeros() as usize;
+            bits &= bits - 1;
+            let step = &steps[k];
+            if step.axis == Axis::Descendant {
+                active |= 1 << k;
+            }
+            match step.test {
+                Test::Name(test) if test == name => {}
+                Test::Any => {}
+                // only `//@x` reaches the attributes of descendants, `/@x` would be those
+                // of the document
+                Test::Attr(_) => {
+                    if step.axis == Axis::Descendant {
+                        attr_steps |= 1 << k;
+                    }
+                    continue;
+                }
+                _ => continue,
+            }
+
+            let matches = step.predicates.iter().enumerate().all(|(i, predicate)| {
+                let position = match frame.counters.iter_mut().find(|(key, _)| *key == (k, i)) {
+                    Some((_, count)) => {
+                        *count += 1;
+                        *count
+                    }
+                    None => {
+                        frame.counters.push(((k, i), 1));
+                        1
+                    }
+                };
+                let context = StreamContext {
+                    attrs: &self.attrs,
+                    position,
+                };
+                predicate_truth(predicate, &context)
+            });
+            if !matches {
+                continue;
+            }
+
+            match steps.get(k + 1) {
+                None => element = true,
+                Some(next) => {
+                    if matches!(next.test, Test::Attr(_)) {
+                        attr_steps |= 1 << (k + 1);
+                    }
+                    if !matches!(next.test, Test::Attr(_)) || next.axis == Axis::Descendant {
+                        active |= 1 << (k + 1);
+                    }
+                }
+            }
+        }
+
+        if element {
+            let src = self.iter.input.source();
+            let end = match scan::element_bounds(src.as_bytes(), start + 1) {
+                Ok(bounds) | Err(bounds) => bounds.end,
+            };
+            self.queue.push_back(StreamMatch::Element {
+                name,
+                outer_xml: &src[start..end],
+            });
+        }
+        if attr_steps != 0 {
+            for &(attr, value) in &self.attrs {
+                let mut bits = attr_steps;
+                while bits != 0 {
+                    let k = bits.trailing_zeros() as usize;
+                    bits &= bits - 1;
+                    if let Test::Attr(test) = steps[k].test {
+                        if test.is_none_or(|test| test == attr) {
+                            self.queue
+                                .push_back(StreamMatch::Attr { name: attr, value });
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        if name.starts_with('?') || name.starts_with('!') {
+            return false;
+        }
+        self.frames.push(Frame {
+            active,
+            counters: Vec::new(),
+        });
+        true
+    }
+}
+
+struct Parser<'q> {
+    src: &'q str,
+    pos: usize,
+}
+
+impl<'q> Parser<'q> {
+    fn error(&self, message: &'static str) -> QueryError {
+        QueryError {
+            position: self.pos,
+            message,
+        }
+    }
+
+    fn rest(&self) -> &'q str {
+        &self.src[self.pos..]
+    }
+
+    fn ws(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.ws();
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str, message: &'static str) -> Result<(), QueryError> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error(message))
+        }
+    }
+
+    fn peek_name(&mut self) -> Option<&'q str> {
+        self.ws();
+        let rest = self.rest();
+        let mut chars = rest.char_indices();
+        match chars.next() {
+            Some((_, ch)) if ch.is_alphabetic() || ch == '_' => {}
+            _ => return None,
+        }
+        let end = chars
+            .find(|&(_, ch)| !(ch.is_alphanumeric() || matches!(ch, '-' | '_' | '.' | ':')))
+            .map_or(rest.len(), |(i, _)| i);
+        Some(&rest[..end])
+    }
+
+    fn name(&mut self) -> Option<&'q str> {
+        let name = self.peek_name()?;
+        self.pos += name.len();
+        Some(name)
+    }
+
+    fn path(&mut self) -> Result<Path<'q>, QueryError> {
+        let mut absolute = false;
+        let mut axis = Axis::Child;
+        if self.eat("//") {
+            absolute = true;
+            axis = Axis::Descendant;
+        } else if self.eat("/") {
+            absolute = true;
+        }
+
+        let mut steps = Vec::new();
+        loop {
+            steps.push(self.step(axis)?);
+            if self.eat("//") {
+                axis = Axis::Descendant;
+            } else if self.eat("/") {
+                axis = Axis::Child;
+            } else {
+                break;
+            }
+        }
+        Ok(Path { absolute, steps })
+    }
+
+    fn step(&mut self, axis: Axis) -> Result<Step<'q>, QueryError> {
+        let test = if self.eat("..") {
+            Test::Parent
+        } else if self.eat(".") {
+            Test::SelfNode
+        } else if self.eat("@") {
+            if self.eat("*") {
+                Test::Attr(None)
+            } else {
+                Test::Attr(Some(
+                    self.name().ok_or(self.error("expected attribute name"))?,
+                ))
+            }
+        } else if self.eat("*") {
+            Test::Any
+        } else {
+            let name = self.name().ok_or(self.error("expected step"))?;
+            if name == "text" && self.eat("(") {
+                self.expect(")", "expected `)`")?;
+                Test::Text
+            } else {
+                Test::Name(name)
+            }
+        };
+
+        let mut predicates = Vec::new();
+        while self.eat("[") {
+            predicates.push(self.expr()?);
+            self.expect("]", "expected `]`")?;
+        }
+        Ok(Step {
+            axis,
+            test,
+            predicates,
+        })
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        if self.peek_name() == Some(keyword) {
+            self.pos += keyword.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr<'q>, QueryError> {
+        let mut expr = self.and_expr()?;
+        while self.keyword("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and_expr()?));
+        }
+        Ok(expr)
+    }
+
+    fn and_expr(&mut self) -> Result<Expr<'q>, QueryError> {
+        let mut expr = self.cmp_expr()?;
+        while self.keyword("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.cmp_expr()?));
+        }
+        Ok(expr)
+    }
+
+    fn cmp_expr(&mut self) -> Result<Expr<'q>, QueryError> {
+        let expr = self.primary()?;
+        let op = [
+            ("!=", Op::Ne),
+            ("<=", Op::Le),
+            (">=", Op::Ge),
+            ("=", Op::Eq),
+            ("<", Op::Lt),
+            (">", Op::Gt),
+        ]
+        .into_iter()
+        .find(|(token, _)| self.eat(token));
+        match op {
+            Some((_, op)) => Ok(Expr::Cmp(Box::new(expr), op, Box::new(self.primary()?))),
+            None => Ok(expr),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr<'q>, QueryError> {
+        self.ws();
+        let rest = self.rest();
+        if self.eat("(") {
+            let expr = self.expr()?;
+            self.expect(")", "expected `)`")?;
+            return Ok(expr);
+        }
+        if let Some(quote) = rest.chars().next().filter(|&ch| ch == '"' || ch == '\'') {
+            let end = rest[1..]
+                .find(quote)
+                .ok_or(self.error("unterminated string"))?;
+            self.pos += end + 2;
+            return Ok(Expr::String(&rest[1..end + 1]));
+        }
+        let digits = rest
+            .find(|ch: char| !(ch.is_ascii_digit() || ch == '.'))
+            .unwrap_or(rest.len());
+        if digits > 0 && rest[..digits] != *"." && rest[..digits] != *".." {
+            let n = rest[..digits]
+                .parse()
+                .map_err(|_| self.error("invalid number"))?;
+            self.pos += digits;
+            return Ok(Expr::Number(n));
+        }
+
+        if let Some(name) = self.peek_name() {
+            if self.rest()[name.len()..].trim_start().starts_with('(') && name != "text" {
+                let start = self.pos;
+                self.pos += name.len();
+                self.expect("(", "expected `(`")?;
+                let expr = match name {
+                    "position" => Expr::Position,
+                    "last" => Expr::Last,
+                    "not" => Expr::Not(Box::new(self.expr()?)),
+                    _ => {
+                        self.pos = start;
+                        return Err(self.error("unknown function"));
+                    }
+                };
+                self.expect(")", "expected `)`")?;
+                return Ok(expr);
+            }
+        }
+        Ok(Expr::Path(self.path()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(query: &str, src: &str) -> Vec<String> {
+        let doc = Document::parse(src);
+        Query::parse(query)
+            .unwrap()
+            .select(&doc)
+            .iter()
+            .map(|item| item.string_value().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn dom() {
+        let src = "<r><a x=\"1\">one<b>two</b></a><a x=\"2\" y/><c><a x=\"3\">three</a></c></r>";
+        assert_eq!(values("/r/a/@x", src), ["1", "2"]);
+        assert_eq!(values("//a/@x", src), ["1", "2", "3"]);
+        assert_eq!(values("//a[2]/@x", src), ["2"]);
+        assert_eq!(values("//a[last()]/@x", src), ["2", "3"]);
+        assert_eq!(values("/r/a[@y]/@x", src), ["2"]);
+        assert_eq!(values("//a[not(@y) and @x != 3]/@x", src), ["1"]);
+        assert_eq!(values("//a[@x >= 2 or b]/@x", src), ["1", "2", "3"]);
+        assert_eq!(values("//a[b = 'two']/text()", src), ["one"]);
+        assert_eq!(values("/r/a[1]", src), ["onetwo"]);
+        assert_eq!(values("//b/../@x", src), ["1"]);
+        assert_eq!(values("//c/./a/@*", src), ["3"]);
+        assert_eq!(values("/r/*[position() > 2]/a/text()", src), ["three"]);
+        assert_eq!(values("//a[@x = 1][1]//text()", src), ["one", "two"]);
+        assert_eq!(values("/a", src), [] as [&str; 0]);
+
+        // relative to a node
+        let doc = Document::parse(src);
+        let c = doc.root().unwrap().last_child().unwrap();
+        let query = Query::parse("a/@x").unwrap();
+        assert_eq!(query.select_from(c)[0].string_value(), "3");
+    }
+
+    #[test]
+    fn errors() {
+        for (query, position) in [
+            ("", 0),
+            ("/a[", 3),
+            ("/a[@x = 'b]", 8),
+            ("/a[foo()]", 3),
+            ("/a]", 2),
+            ("/a/@", 4),
+        ] {
+            assert_eq!(
+                Query::parse(query).unwrap_err().position,
+                position,
+                "{}",
+                query
+            );
+        }
+        let query = Query::parse("/a[last()]").unwrap();
+        assert!(!query.is_streamable());
+        assert!(query.stream(XmlIter::from("<a/>")).is_err());
+        assert!(!Query::parse("/a[b]").unwrap().is_streamable());
+        assert!(!Query::parse("/a/@b/c").unwrap().is_streamable());
+        assert!(Query::parse("//a[@b > 1][2]/text()")
+            .unwrap()
+            .is_streamable());
+    }
+
+    #[test]
+    fn huge() {
+        let src = include_str!("../benches/files/huge.xml");
+        let query = Query::parse("/response/summary/types/type[@count > 1000]/@name").unwrap();
+        let doc = Document::parse(src);
+        let names: Vec<_> = query
+            .select(&doc)
+            .iter()
+            .map(|i| i.string_value())
+            .collect();
+        assert_eq!(names.len(), 9);
+        assert_eq!(names[0], "service");
+
+        let streamed: Vec<_> = query
+            .stream(XmlIter::from(src))
+            .unwrap()
+            .map(|found| match found {
+                StreamMatch::Attr { value, .. } => value.unwrap(),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(names, streamed);
+    }
+
+    #[test]
+    fn stream_like_dom() {
+        for (src, query) in [
+            (include_str!("../benches/files/medium.svg"), "//g"),
+            (include_str!("../benches/files/medium.svg"), "//stop[2]"),
+            (
+                include_str!("../benches/files/medium.svg"),
+                "/svg/g//path[@id]/@d",
+            ),
+            (
+                include_str!("../benches/files/medium.svg"),
+                "//*[@id = 'layer2']//@style",
+            ),
+            (
+                include_str!("../benches/files/medium.svg"),
+                "//filter[position() < 3 and @id]/*",
+            ),
+            (include_str!("../benches/files/medium.svg"), "//@*"),
+            (include_str!("../benches/files/medium.svg"), "/svg/@*"),
+            (
+                include_str!("../benches/files/fonts.conf"),
+                "/fontconfig/match/edit/*/text()",
+            ),
+            (
+                include_str!("../benches/files/fonts.conf"),
+                "//match[@target = 'font'][2]//text()",
+            ),
+            (
+                include_str!("../benches/files/fonts.conf"),
+                "//edit[@name != 'hinting']",
+            ),
+        ] {
+            let doc = Document::parse(src);
+            let query = Query::parse(query).unwrap();
+            let expected: Vec<StreamMatch> = query
+                .select(&doc)
+                .iter()
+                .map(|item| match *item {
+                    Item::Node(node) => match node.kind() {
+                        NodeKind::Element { name } => StreamMatch::Element {
+                            name,
+                            outer_xml: "",
+                        },
+                        NodeKind::Text { text } => StreamMatch::Text { text },
+                        _ => unreachable!(),
+                    },
+                    Item::Attr { attr, .. } => StreamMatch::Attr {
+                        name: attr.name,
+                        value: attr.value,
+                    },
+                })
+                .collect();
+            let streamed: Vec<StreamMatch> = query
+                .stream(XmlIter::from(src))
+                .unwrap()
+                .map(|found| match found {
+                    StreamMatch::Element { name, outer_xml } => {
+                        assert!(outer_xml.starts_with('<') && outer_xml.ends_with('>'));
+                        StreamMatch::Element {
+                            name,
+                            outer_xml: "",
+                        }
+                    }
+                    found => found,
+                })
+                .collect();
+            assert!(!expected.is_empty(), "{:?}", query);
+            assert_eq!(expected, streamed, "{:?}", query);
+        }
+
+        // the document has no attributes
+        let src = "<r x=\"1\"><a x=\"2\"/></r>";
+        let query = Query::parse("/@x").unwrap();
+        assert!(query.select(&Document::parse(src)).is_empty());
+        assert_eq!(query.stream(XmlIter::from(src)).unwrap().count(), 0);
+    }
+}